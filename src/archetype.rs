@@ -0,0 +1,689 @@
+//! Storing entities with the same set of components together in tables.
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    iter::Copied,
+    marker::PhantomData,
+    mem, slice, vec,
+};
+
+use crate::Entity;
+
+/// A type erased column of an archetype, always a `Vec<T>`.
+trait Column: Any {
+    /// Creates an empty column of the same type.
+    fn empty(&self) -> Box<dyn Column>;
+
+    /// Moves the component at `row` to the end of `to`,
+    /// replacing it with the last component of this column.
+    fn move_row(&mut self, row: usize, to: &mut dyn Column);
+
+    /// Drops the component at `row`, replacing it with the last component of this column.
+    fn remove_row(&mut self, row: usize);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> Column for Vec<T> {
+    fn empty(&self) -> Box<dyn Column> {
+        Box::new(Vec::<T>::new())
+    }
+
+    fn move_row(&mut self, row: usize, to: &mut dyn Column) {
+        let c = self.swap_remove(row);
+        downcast_mut::<T>(to).push(c);
+    }
+
+    fn remove_row(&mut self, row: usize) {
+        self.swap_remove(row);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn downcast<T: 'static>(column: &dyn Column) -> &Vec<T> {
+    column
+        .as_any()
+        .downcast_ref()
+        .expect("mismatched column type")
+}
+
+fn downcast_mut<T: 'static>(column: &mut dyn Column) -> &mut Vec<T> {
+    column
+        .as_any_mut()
+        .downcast_mut()
+        .expect("mismatched column type")
+}
+
+/// A table storing all entities with exactly the components in `types`.
+struct Archetype {
+    /// The component types of this archetype, sorted.
+    types: Vec<TypeId>,
+    /// The column of each type in `types`.
+    columns: Vec<Box<dyn Column>>,
+    /// The entity of each row.
+    entities: Vec<Entity>,
+}
+
+impl fmt::Debug for Archetype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The columns are type erased, so their components can't be shown.
+        f.debug_struct("Archetype")
+            .field("types", &self.types)
+            .field("entities", &self.entities)
+            .finish()
+    }
+}
+
+impl Archetype {
+    fn column(&self, ty: TypeId) -> Option<usize> {
+        self.types.binary_search(&ty).ok()
+    }
+
+    /// Borrows the columns with the given types, returning `None`
+    /// if this archetype does not have all of them.
+    fn borrow(&self, types: &[(TypeId, bool)]) -> Option<(&[Entity], Vec<Borrowed<'_>>)> {
+        let columns = types
+            .iter()
+            .map(|&(ty, _)| {
+                Some(Borrowed(ColumnRef::Shared(
+                    &*self.columns[self.column(ty)?],
+                )))
+            })
+            .collect::<Option<_>>()?;
+        Some((&self.entities, columns))
+    }
+
+    /// Like `borrow`, except that types with `true` are borrowed mutably.
+    fn borrow_mut(&mut self, types: &[(TypeId, bool)]) -> Option<(&[Entity], Vec<Borrowed<'_>>)> {
+        let indices = types
+            .iter()
+            .map(|&(ty, _)| self.column(ty))
+            .collect::<Option<Vec<_>>>()?;
+        let mut borrowed: Vec<_> = types.iter().map(|_| None).collect();
+        for (i, column) in self.columns.iter_mut().enumerate() {
+            if let Some(q) = indices.iter().position(|&j| j == i) {
+                borrowed[q] = Some(Borrowed(if types[q].1 {
+                    ColumnRef::Unique(&mut **column)
+                } else {
+                    ColumnRef::Shared(&**column)
+                }));
+            }
+        }
+
+        let columns = borrowed.into_iter().map(Option::unwrap).collect();
+        Some((&self.entities, columns))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    archetype: usize,
+    row: usize,
+}
+
+/// A storage for all components of each entity, grouping entities with
+/// the same set of components into shared tables, called archetypes.
+///
+/// Unlike joining multiple storages, queries only visit entities which have
+/// all requested components, iterating over each matching table linearly.
+/// Adding or removing components moves an entity to a different table,
+/// which is more expensive than inserting into a single storage.
+///
+/// As it is not the storage of a single component type, this does not
+/// implement [`ComponentStorage`].
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{archetype::Archetypes, Entity};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Position(f32);
+/// struct Velocity(f32);
+///
+/// let mut world = Archetypes::new();
+/// world.insert(Entity(0), Position(0.0));
+/// world.insert(Entity(0), Velocity(2.0));
+/// world.insert(Entity(1), Position(5.0));
+///
+/// for (position, velocity) in world.query_mut::<(&mut Position, &Velocity)>() {
+///     position.0 += velocity.0;
+/// }
+/// assert_eq!(world.get::<Position>(Entity(0)), Some(&Position(2.0)));
+///
+/// let positions: Vec<_> = world.query::<(Entity, &Position)>().map(|(e, _)| e).collect();
+/// assert_eq!(positions.len(), 2);
+/// ```
+///
+/// [`ComponentStorage`]: ../component/trait.ComponentStorage.html
+#[derive(Debug, Default)]
+pub struct Archetypes {
+    archetypes: Vec<Archetype>,
+    /// The archetype of each set of component types.
+    index: HashMap<Vec<TypeId>, usize>,
+    locations: Vec<Option<Location>>,
+    /// The number of entities with at least one component.
+    len: usize,
+}
+
+impl Archetypes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of entities with at least one component.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of distinct sets of components.
+    pub fn archetype_count(&self) -> usize {
+        self.archetypes.len()
+    }
+
+    fn location(&self, entity: Entity) -> Option<Location> {
+        self.locations.get(entity.0).copied().flatten()
+    }
+
+    /// Returns the component of type `T` of `entity` in case it exists.
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        let location = self.location(entity)?;
+        let archetype = &self.archetypes[location.archetype];
+        let column = archetype.column(TypeId::of::<T>())?;
+        Some(&downcast::<T>(&*archetype.columns[column])[location.row])
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        let location = self.location(entity)?;
+        let archetype = &mut self.archetypes[location.archetype];
+        let column = archetype.column(TypeId::of::<T>())?;
+        Some(&mut downcast_mut::<T>(&mut *archetype.columns[column])[location.row])
+    }
+
+    /// Returns `true` if `entity` has a component of type `T`.
+    pub fn contains<T: 'static>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    /// Inserts a component for `entity`, moving it to a different
+    /// archetype if it did not have a component of this type.
+    ///
+    /// In case the component was already present the previous
+    /// one is returned.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, c: T) -> Option<T> {
+        if let Some(previous) = self.get_mut::<T>(entity) {
+            return Some(mem::replace(previous, c));
+        }
+
+        let location = self.location(entity);
+        let mut types =
+            location.map_or_else(Vec::new, |l| self.archetypes[l.archetype].types.clone());
+        let ty = TypeId::of::<T>();
+        let pos = types.binary_search(&ty).unwrap_err();
+        types.insert(pos, ty);
+
+        let target = self.archetype(types, location, Some(Box::new(Vec::<T>::new())));
+        let archetype = &mut self.archetypes[target];
+        let column = archetype.column(ty).unwrap();
+        downcast_mut::<T>(&mut *archetype.columns[column]).push(c);
+        match location {
+            Some(location) => self.move_entity(entity, location, target),
+            None => {
+                self.len += 1;
+                self.push_entity(entity, target);
+            }
+        }
+        None
+    }
+
+    /// Removes the component of type `T` of `entity`, moving it
+    /// to a different archetype.
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        let location = self.location(entity)?;
+        let ty = TypeId::of::<T>();
+        let archetype = &mut self.archetypes[location.archetype];
+        let column = archetype.column(ty)?;
+        let c = downcast_mut::<T>(&mut *archetype.columns[column]).swap_remove(location.row);
+
+        let mut types = archetype.types.clone();
+        types.remove(column);
+        if types.is_empty() {
+            self.remove_entity(location);
+            self.locations[entity.0] = None;
+            self.len -= 1;
+        } else {
+            let target = self.archetype(types, Some(location), None);
+            self.move_entity(entity, location, target);
+        }
+        Some(c)
+    }
+
+    /// Removes all components of `entity`, returning `false`
+    /// if it did not have any.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        match self.location(entity) {
+            Some(location) => {
+                for column in self.archetypes[location.archetype].columns.iter_mut() {
+                    column.remove_row(location.row);
+                }
+                self.remove_entity(location);
+                self.locations[entity.0] = None;
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Iterates over all entities matching the query `Q`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `Q` contains the same component type more than once.
+    pub fn query<'a, Q: ReadOnlyQuery<'a>>(&'a self) -> QueryIter<'a, Q> {
+        QueryIter::new(Tables::Shared(self.archetypes.iter()))
+    }
+
+    /// Iterates over all entities matching the query `Q`,
+    /// which may contain mutable references.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `Q` contains the same component type more than once.
+    pub fn query_mut<'a, Q: Query<'a>>(&'a mut self) -> QueryIter<'a, Q> {
+        QueryIter::new(Tables::Unique(self.archetypes.iter_mut()))
+    }
+
+    /// Iterates over all archetypes matching the query `Q`, returning
+    /// the components of each archetype as contiguous slices.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{archetype::Archetypes, Entity};
+    ///
+    /// let mut world = Archetypes::new();
+    /// for i in 0..4 {
+    ///     world.insert(Entity(i), i as f32);
+    ///     world.insert(Entity(i), 1.5f64);
+    /// }
+    /// world.insert(Entity(0), "player");
+    ///
+    /// let mut lengths = Vec::new();
+    /// for (a, b) in world.query_chunks_mut::<(&mut f32, &f64)>() {
+    ///     lengths.push(a.len());
+    ///     for (a, b) in a.iter_mut().zip(b) {
+    ///         *a += *b as f32;
+    ///     }
+    /// }
+    /// lengths.sort_unstable();
+    /// assert_eq!(lengths, vec![1, 3]);
+    /// assert_eq!(world.get::<f32>(Entity(3)), Some(&4.5));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `Q` contains the same component type more than once.
+    pub fn query_chunks<'a, Q: ReadOnlyQuery<'a>>(&'a self) -> QueryChunks<'a, Q> {
+        QueryChunks::new(Tables::Shared(self.archetypes.iter()))
+    }
+
+    /// Like [`query_chunks`], except that `Q` may contain mutable references.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `Q` contains the same component type more than once.
+    ///
+    /// [`query_chunks`]: struct.Archetypes.html#method.query_chunks
+    pub fn query_chunks_mut<'a, Q: Query<'a>>(&'a mut self) -> QueryChunks<'a, Q> {
+        QueryChunks::new(Tables::Unique(self.archetypes.iter_mut()))
+    }
+
+    /// Returns the archetype with the given `types`, creating it if necessary.
+    ///
+    /// The columns of a new archetype are created using the columns of `from`,
+    /// and `column` for the one type `from` does not have.
+    fn archetype(
+        &mut self,
+        types: Vec<TypeId>,
+        from: Option<Location>,
+        mut column: Option<Box<dyn Column>>,
+    ) -> usize {
+        if let Some(&idx) = self.index.get(&types) {
+            return idx;
+        }
+
+        let from = from.map(|l| &self.archetypes[l.archetype]);
+        let columns = types
+            .iter()
+            .map(
+                |&ty| match from.and_then(|a| Some(&a.columns[a.column(ty)?])) {
+                    Some(c) => c.empty(),
+                    None => column.take().expect("missing column"),
+                },
+            )
+            .collect();
+        self.archetypes.push(Archetype {
+            types: types.clone(),
+            columns,
+            entities: Vec::new(),
+        });
+        self.index.insert(types, self.archetypes.len() - 1);
+        self.archetypes.len() - 1
+    }
+
+    /// Moves all components of `entity` at `from` which exist in `to` to the end of `to`.
+    ///
+    /// Columns which do not exist in `to` must already have had `from.row` removed.
+    fn move_entity(&mut self, entity: Entity, from: Location, to: usize) {
+        let (source, target) = if from.archetype < to {
+            let (start, end) = self.archetypes.split_at_mut(to);
+            (&mut start[from.archetype], &mut end[0])
+        } else {
+            let (start, end) = self.archetypes.split_at_mut(from.archetype);
+            (&mut end[0], &mut start[to])
+        };
+
+        for (ty, column) in source.types.iter().zip(source.columns.iter_mut()) {
+            if let Some(i) = target.column(*ty) {
+                column.move_row(from.row, &mut *target.columns[i]);
+            }
+        }
+
+        self.remove_entity(from);
+        self.push_entity(entity, to);
+    }
+
+    /// Adds `entity` as the last row of `archetype`, whose columns already contain it.
+    fn push_entity(&mut self, entity: Entity, archetype: usize) {
+        let entities = &mut self.archetypes[archetype].entities;
+        entities.push(entity);
+        if entity.0 >= self.locations.len() {
+            self.locations.resize(entity.0 + 1, None);
+        }
+        self.locations[entity.0] = Some(Location {
+            archetype,
+            row: entities.len() - 1,
+        });
+    }
+
+    /// Removes the entity at `location`, whose components have already been removed.
+    fn remove_entity(&mut self, location: Location) {
+        let entities = &mut self.archetypes[location.archetype].entities;
+        entities.swap_remove(location.row);
+        if let Some(&moved) = entities.get(location.row) {
+            self.locations[moved.0] = Some(location);
+        }
+    }
+}
+
+/// A borrowed column of an archetype.
+#[doc(hidden)]
+pub struct Borrowed<'a>(ColumnRef<'a>);
+
+enum ColumnRef<'a> {
+    Shared(&'a dyn Column),
+    Unique(&'a mut dyn Column),
+}
+
+/// The archetypes visited by a query.
+enum Tables<'a> {
+    Shared(slice::Iter<'a, Archetype>),
+    Unique(slice::IterMut<'a, Archetype>),
+}
+
+impl<'a> Tables<'a> {
+    /// Returns the next archetype which has all `types`.
+    fn next(&mut self, types: &[(TypeId, bool)]) -> Option<(&'a [Entity], Vec<Borrowed<'a>>)> {
+        match self {
+            Tables::Shared(iter) => iter.find_map(|a| a.borrow(types)),
+            Tables::Unique(iter) => iter.find_map(|a| a.borrow_mut(types)),
+        }
+    }
+}
+
+/// The components accessed by [`Archetypes::query`].
+///
+/// This is implemented for `Entity`, `&T`, `&mut T` and tuples of queries.
+///
+/// [`Archetypes::query`]: struct.Archetypes.html#method.query
+pub trait Query<'a> {
+    type Item;
+    #[doc(hidden)]
+    type Iter: Iterator<Item = Self::Item>;
+    /// The components of an entire archetype.
+    type Chunk;
+
+    /// Adds the accessed component types, and whether they are accessed mutably.
+    #[doc(hidden)]
+    fn types(types: &mut Vec<(TypeId, bool)>);
+
+    /// Iterates over one archetype, taking the columns added in `types`.
+    #[doc(hidden)]
+    fn fetch(entities: &'a [Entity], columns: &mut vec::IntoIter<Borrowed<'a>>) -> Self::Iter;
+
+    /// Like `fetch`, except that the columns are returned as slices.
+    #[doc(hidden)]
+    fn fetch_chunk(
+        entities: &'a [Entity],
+        columns: &mut vec::IntoIter<Borrowed<'a>>,
+    ) -> Self::Chunk;
+}
+
+/// A query which does not mutate any components.
+pub trait ReadOnlyQuery<'a>: Query<'a> {}
+
+impl<'a> Query<'a> for Entity {
+    type Item = Entity;
+    type Iter = Copied<slice::Iter<'a, Entity>>;
+    type Chunk = &'a [Entity];
+
+    fn types(_: &mut Vec<(TypeId, bool)>) {}
+
+    fn fetch(entities: &'a [Entity], columns: &mut vec::IntoIter<Borrowed<'a>>) -> Self::Iter {
+        Self::fetch_chunk(entities, columns).iter().copied()
+    }
+
+    fn fetch_chunk(entities: &'a [Entity], _: &mut vec::IntoIter<Borrowed<'a>>) -> &'a [Entity] {
+        entities
+    }
+}
+
+impl<'a> ReadOnlyQuery<'a> for Entity {}
+
+impl<'a, T: 'static> Query<'a> for &'a T {
+    type Item = &'a T;
+    type Iter = slice::Iter<'a, T>;
+    type Chunk = &'a [T];
+
+    fn types(types: &mut Vec<(TypeId, bool)>) {
+        types.push((TypeId::of::<T>(), false));
+    }
+
+    fn fetch(entities: &'a [Entity], columns: &mut vec::IntoIter<Borrowed<'a>>) -> Self::Iter {
+        Self::fetch_chunk(entities, columns).iter()
+    }
+
+    fn fetch_chunk(_: &'a [Entity], columns: &mut vec::IntoIter<Borrowed<'a>>) -> &'a [T] {
+        match columns.next() {
+            Some(Borrowed(ColumnRef::Shared(column))) => downcast::<T>(column),
+            Some(Borrowed(ColumnRef::Unique(column))) => downcast::<T>(column),
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<'a, T: 'static> ReadOnlyQuery<'a> for &'a T {}
+
+impl<'a, T: 'static> Query<'a> for &'a mut T {
+    type Item = &'a mut T;
+    type Iter = slice::IterMut<'a, T>;
+    type Chunk = &'a mut [T];
+
+    fn types(types: &mut Vec<(TypeId, bool)>) {
+        types.push((TypeId::of::<T>(), true));
+    }
+
+    fn fetch(entities: &'a [Entity], columns: &mut vec::IntoIter<Borrowed<'a>>) -> Self::Iter {
+        Self::fetch_chunk(entities, columns).iter_mut()
+    }
+
+    fn fetch_chunk(_: &'a [Entity], columns: &mut vec::IntoIter<Borrowed<'a>>) -> &'a mut [T] {
+        match columns.next() {
+            Some(Borrowed(ColumnRef::Unique(column))) => downcast_mut::<T>(column),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The iterator over a single archetype created by a tuple of queries.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct TupleIter<T>(T);
+
+macro_rules! query_tuple {
+    ($($par:ident $var:ident),*) => {
+        impl<$($par: Iterator),*> Iterator for TupleIter<($($par),*)> {
+            type Item = ($($par::Item),*);
+
+            fn next(&mut self) -> Option<Self::Item> {
+                let ($($var),*) = &mut self.0;
+                Some(($($var.next()?),*))
+            }
+        }
+
+        impl<'a, $($par: Query<'a>),*> Query<'a> for ($($par),*) {
+            type Item = ($($par::Item),*);
+            type Iter = TupleIter<($($par::Iter),*)>;
+            type Chunk = ($($par::Chunk),*);
+
+            fn types(types: &mut Vec<(TypeId, bool)>) {
+                $($par::types(types);)*
+            }
+
+            fn fetch(entities: &'a [Entity], columns: &mut vec::IntoIter<Borrowed<'a>>) -> Self::Iter {
+                TupleIter(($($par::fetch(entities, columns)),*))
+            }
+
+            fn fetch_chunk(
+                entities: &'a [Entity],
+                columns: &mut vec::IntoIter<Borrowed<'a>>,
+            ) -> Self::Chunk {
+                ($($par::fetch_chunk(entities, columns)),*)
+            }
+        }
+
+        impl<'a, $($par: ReadOnlyQuery<'a>),*> ReadOnlyQuery<'a> for ($($par),*) {}
+    };
+}
+
+query_tuple!(A a, B b);
+query_tuple!(A a, B b, C c);
+query_tuple!(A a, B b, C c, D d);
+query_tuple!(A a, B b, C c, D d, E e);
+query_tuple!(A a, B b, C c, D d, E e, F f);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o);
+query_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o, P p);
+
+/// The iterator returned by [`Archetypes::query`] and [`Archetypes::query_mut`].
+///
+/// [`Archetypes::query`]: struct.Archetypes.html#method.query
+/// [`Archetypes::query_mut`]: struct.Archetypes.html#method.query_mut
+pub struct QueryIter<'a, Q: Query<'a>> {
+    tables: Tables<'a>,
+    types: Vec<(TypeId, bool)>,
+    current: Option<Q::Iter>,
+}
+
+/// Returns the component types accessed by `Q`.
+fn query_types<'a, Q: Query<'a>>() -> Vec<(TypeId, bool)> {
+    let mut types = Vec::new();
+    Q::types(&mut types);
+    for (i, (ty, _)) in types.iter().enumerate() {
+        assert!(
+            types[..i].iter().all(|(t, _)| t != ty),
+            "a query must not contain the same component type more than once"
+        );
+    }
+    types
+}
+
+impl<'a, Q: Query<'a>> QueryIter<'a, Q> {
+    fn new(tables: Tables<'a>) -> Self {
+        QueryIter {
+            tables,
+            types: query_types::<Q>(),
+            current: None,
+        }
+    }
+}
+
+impl<'a, Q: Query<'a>> Iterator for QueryIter<'a, Q> {
+    type Item = Q::Item;
+
+    fn next(&mut self) -> Option<Q::Item> {
+        loop {
+            if let Some(item) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(item);
+            }
+
+            let (entities, columns) = self.tables.next(&self.types)?;
+            self.current = Some(Q::fetch(entities, &mut columns.into_iter()));
+        }
+    }
+}
+
+/// The iterator returned by [`Archetypes::query_chunks`] and [`Archetypes::query_chunks_mut`].
+///
+/// [`Archetypes::query_chunks`]: struct.Archetypes.html#method.query_chunks
+/// [`Archetypes::query_chunks_mut`]: struct.Archetypes.html#method.query_chunks_mut
+pub struct QueryChunks<'a, Q: Query<'a>> {
+    tables: Tables<'a>,
+    types: Vec<(TypeId, bool)>,
+    _query: PhantomData<Q>,
+}
+
+impl<'a, Q: Query<'a>> QueryChunks<'a, Q> {
+    fn new(tables: Tables<'a>) -> Self {
+        QueryChunks {
+            tables,
+            types: query_types::<Q>(),
+            _query: PhantomData,
+        }
+    }
+}
+
+impl<'a, Q: Query<'a>> Iterator for QueryChunks<'a, Q> {
+    type Item = Q::Chunk;
+
+    fn next(&mut self) -> Option<Q::Chunk> {
+        loop {
+            let (entities, columns) = self.tables.next(&self.types)?;
+            if !entities.is_empty() {
+                return Some(Q::fetch_chunk(entities, &mut columns.into_iter()));
+            }
+        }
+    }
+}
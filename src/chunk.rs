@@ -0,0 +1,138 @@
+//! Joining runs of contiguous components as slices.
+use std::ops::Range;
+
+/// Storages which keep their components in contiguous slices,
+/// allowing them to be joined one run at a time.
+///
+/// Positions are relative to the current front of the storage, which
+/// moves forward when calling `skip_run` or `take_run`. This is implemented for the
+/// columns of a [`soa_storage!`], as `Storage<T>` keeps its components
+/// as `Option<T>` which can not be returned as a slice of `T`.
+/// Archetypes are always contiguous, see [`Archetypes::query_chunks`].
+///
+/// [`soa_storage!`]: ../macro.soa_storage.html
+/// [`Archetypes::query_chunks`]: ../archetype/struct.Archetypes.html#method.query_chunks
+pub trait ChunkJoin: Sized {
+    type Chunk;
+
+    /// Returns the next run of positions with a component, starting at or after `from`.
+    ///
+    /// In case `from` is inside of a run, the returned range starts at `from`.
+    fn next_run(&self, from: usize) -> Option<Range<usize>>;
+
+    /// Skips the next `n` positions.
+    fn skip_run(&mut self, n: usize);
+
+    /// Returns the components of the next `n` positions, which must all have a component.
+    fn take_run(&mut self, n: usize) -> Self::Chunk;
+
+    /// Iterates over all runs of entities which have a component in
+    /// every joined storage, returning a slice of each storage per run.
+    ///
+    /// This allows inner loops to be auto-vectorized.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{chunk::ChunkJoin, soa_storage, Entity};
+    ///
+    /// soa_storage! {
+    ///     pub mod body {
+    ///         pub struct Body {
+    ///             pub position: f32,
+    ///             pub velocity: f32,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut bodies = body::Storage::new();
+    /// for i in [0, 1, 2, 5, 6] {
+    ///     bodies.insert(Entity(i), body::Body { position: 0.0, velocity: i as f32 });
+    /// }
+    ///
+    /// let columns = bodies.columns_mut();
+    /// let mut lengths = Vec::new();
+    /// for (positions, velocities) in (columns.position, columns.velocity).join_chunks() {
+    ///     lengths.push(positions.len());
+    ///     for (p, v) in positions.iter_mut().zip(velocities) {
+    ///         *p += *v;
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(lengths, vec![3, 2]);
+    /// assert_eq!(bodies.get(Entity(6)).map(|b| *b.position), Some(6.0));
+    /// ```
+    fn join_chunks(self) -> JoinedChunks<Self> {
+        JoinedChunks(self)
+    }
+}
+
+/// The iterator returned by [`ChunkJoin::join_chunks`].
+///
+/// [`ChunkJoin::join_chunks`]: trait.ChunkJoin.html#method.join_chunks
+#[derive(Debug, Clone)]
+pub struct JoinedChunks<T>(T);
+
+impl<T: ChunkJoin> Iterator for JoinedChunks<T> {
+    type Item = T::Chunk;
+
+    fn next(&mut self) -> Option<T::Chunk> {
+        let run = self.0.next_run(0)?;
+        self.0.skip_run(run.start);
+        Some(self.0.take_run(run.len()))
+    }
+}
+
+macro_rules! chunk_tuple {
+    ($($par:ident $var:ident),*) => {
+        impl<$($par: ChunkJoin),*> ChunkJoin for ($($par),*) {
+            type Chunk = ($($par::Chunk),*);
+
+            fn next_run(&self, from: usize) -> Option<Range<usize>> {
+                let ($($var),*) = self;
+                let mut start = from;
+                loop {
+                    let mut end = usize::MAX;
+                    let mut aligned = true;
+                    $(
+                        let run = $var.next_run(start)?;
+                        if run.start > start {
+                            start = run.start;
+                            aligned = false;
+                        }
+                        end = end.min(run.end);
+                    )*
+                    if aligned {
+                        return Some(start..end);
+                    }
+                }
+            }
+
+            fn skip_run(&mut self, n: usize) {
+                let ($($var),*) = self;
+                $($var.skip_run(n);)*
+            }
+
+            fn take_run(&mut self, n: usize) -> Self::Chunk {
+                let ($($var),*) = self;
+                ($($var.take_run(n)),*)
+            }
+        }
+    };
+}
+
+chunk_tuple!(A a, B b);
+chunk_tuple!(A a, B b, C c);
+chunk_tuple!(A a, B b, C c, D d);
+chunk_tuple!(A a, B b, C c, D d, E e);
+chunk_tuple!(A a, B b, C c, D d, E e, F f);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o);
+chunk_tuple!(A a, B b, C c, D d, E e, F f, G g, H h, I i, J j, K k, L l, M m, N n, O o, P p);
@@ -0,0 +1,243 @@
+//! Iterating over all combinations of joined entities.
+use std::collections::BTreeMap;
+
+use crate::{Entity, SparseStorage, Storage};
+
+impl<T> Storage<T> {
+    /// Returns all unordered pairs of different components of this storage.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entity, Storage};
+    ///
+    /// let mut bodies = Storage::new();
+    /// for (i, &x) in [0, 4, 10].iter().enumerate() {
+    ///     bodies.insert(Entity(i), (x, 0));
+    /// }
+    ///
+    /// // pull all bodies towards each other
+    /// let mut pairs = bodies.pairs_mut();
+    /// while let Some([(_, a), (_, b)]) = pairs.fetch_next() {
+    ///     let distance = b.0 - a.0;
+    ///     a.1 += distance;
+    ///     b.1 -= distance;
+    /// }
+    /// assert_eq!(bodies.get(Entity(0)), Some(&(0, 14)));
+    /// ```
+    pub fn pairs_mut(&mut self) -> PairsMut<'_, T> {
+        let first = next_some(&self.inner, 0);
+        PairsMut {
+            second: first.unwrap_or(0),
+            first,
+            slots: &mut self.inner,
+        }
+    }
+}
+
+/// All unordered pairs of different components of a `Storage`,
+/// created by [`Storage::pairs_mut`].
+///
+/// Each pair is returned once, with its entities in ascending order.
+/// As both components are mutably borrowed, pairs can only be
+/// borrowed one at a time using [`fetch_next`].
+///
+/// [`Storage::pairs_mut`]: ../struct.Storage.html#method.pairs_mut
+/// [`fetch_next`]: struct.PairsMut.html#method.fetch_next
+#[derive(Debug)]
+pub struct PairsMut<'a, T> {
+    slots: &'a mut [Option<T>],
+    /// The first entity of the previous pair, `None` once all pairs were returned.
+    first: Option<usize>,
+    /// The second entity of the previous pair.
+    second: usize,
+}
+
+impl<'a, T> PairsMut<'a, T> {
+    /// Returns the next pair, mutably borrowing both of its components.
+    pub fn fetch_next(&mut self) -> Option<[(Entity, &mut T); 2]> {
+        let first = loop {
+            let first = self.first?;
+            if let Some(second) = next_some(self.slots, self.second + 1) {
+                self.second = second;
+                break first;
+            }
+
+            self.first = next_some(self.slots, first + 1);
+            self.second = self.first.unwrap_or(0);
+        };
+
+        let (start, end) = self.slots.split_at_mut(self.second);
+        Some([
+            (Entity(first), start[first].as_mut()?),
+            (Entity(self.second), end[0].as_mut()?),
+        ])
+    }
+}
+
+fn next_some<T>(slots: &[Option<T>], from: usize) -> Option<usize> {
+    let rest = slots.get(from..)?;
+    rest.iter().position(Option::is_some).map(|i| from + i)
+}
+
+impl<T> SparseStorage<T> {
+    /// Returns all unordered pairs of different components of this storage.
+    ///
+    /// See [`Storage::pairs_mut`] for more details.
+    ///
+    /// [`Storage::pairs_mut`]: ../struct.Storage.html#method.pairs_mut
+    pub fn pairs_mut(&mut self) -> SparsePairsMut<'_, T> {
+        let first = next_key(&self.inner, 0);
+        SparsePairsMut {
+            second: first.unwrap_or(0),
+            first,
+            inner: &mut self.inner,
+        }
+    }
+}
+
+/// All unordered pairs of different components of a `SparseStorage`,
+/// created by [`SparseStorage::pairs_mut`].
+///
+/// See [`PairsMut`] for more details.
+///
+/// [`SparseStorage::pairs_mut`]: ../struct.SparseStorage.html#method.pairs_mut
+/// [`PairsMut`]: struct.PairsMut.html
+#[derive(Debug)]
+pub struct SparsePairsMut<'a, T> {
+    inner: &'a mut BTreeMap<usize, T>,
+    /// The first entity of the previous pair, `None` once all pairs were returned.
+    first: Option<usize>,
+    /// The second entity of the previous pair.
+    second: usize,
+}
+
+impl<'a, T> SparsePairsMut<'a, T> {
+    /// Returns the next pair, mutably borrowing both of its components.
+    pub fn fetch_next(&mut self) -> Option<[(Entity, &mut T); 2]> {
+        let first = loop {
+            let first = self.first?;
+            if let Some(second) = next_key(self.inner, self.second + 1) {
+                self.second = second;
+                break first;
+            }
+
+            self.first = next_key(self.inner, first + 1);
+            self.second = self.first.unwrap_or(0);
+        };
+
+        // Both ends of a range are distinct entries of the map.
+        let mut range = self.inner.range_mut(first..=self.second);
+        let (_, a) = range.next()?;
+        let (_, b) = range.next_back()?;
+        Some([(Entity(first), a), (Entity(self.second), b)])
+    }
+}
+
+fn next_key<T>(inner: &BTreeMap<usize, T>, from: usize) -> Option<usize> {
+    inner.range(from..).next().map(|(&k, _)| k)
+}
+
+/// All combinations of `N` different items of a join,
+/// created by [`Joinable::iter_combinations`].
+///
+/// Each combination is returned once, with its entities in ascending order.
+/// Instead of collecting the items of the join, this keeps a clone of the
+/// joined iterator for each item of the current combination. For mutable
+/// access to pairs of components, use [`Storage::pairs_mut`] or
+/// [`SparseStorage::pairs_mut`].
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{Entity, Joinable, Storage};
+///
+/// let mut position = Storage::new();
+/// for (i, &x) in [0, 4, 10].iter().enumerate() {
+///     position.insert(Entity(i), x);
+/// }
+///
+/// let entities: Vec<_> = (&position)
+///     .iter_combinations::<2>()
+///     .map(|[(a, _), (b, _)]| (a.0, b.0))
+///     .collect();
+/// assert_eq!(entities, vec![(0, 1), (0, 2), (1, 2)]);
+///
+/// let distances: Vec<_> = (&position)
+///     .iter_combinations::<2>()
+///     .map(|[(_, a), (_, b)]| b - a)
+///     .collect();
+/// assert_eq!(distances, vec![4, 10, 6]);
+/// ```
+///
+/// [`Joinable::iter_combinations`]: ../trait.Joinable.html#method.iter_combinations
+/// [`Storage::pairs_mut`]: ../struct.Storage.html#method.pairs_mut
+/// [`SparseStorage::pairs_mut`]: ../struct.SparseStorage.html#method.pairs_mut
+#[derive(Debug, Clone)]
+pub struct Combinations<I: Iterator, const N: usize> {
+    iter: I,
+    /// For each item of the previous combination, the iterator after it.
+    iters: Vec<I>,
+    /// The items of the previous combination.
+    items: Vec<I::Item>,
+    started: bool,
+}
+
+impl<I: Iterator, const N: usize> Combinations<I, N> {
+    pub(crate) fn new(iter: I) -> Self {
+        Combinations {
+            iter,
+            iters: Vec::with_capacity(N),
+            items: Vec::with_capacity(N),
+            started: false,
+        }
+    }
+}
+
+impl<I, const N: usize> Iterator for Combinations<I, N>
+where
+    I: Iterator + Clone,
+    I::Item: Clone,
+{
+    type Item = [I::Item; N];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut advance = self.started;
+        self.started = true;
+        loop {
+            if advance {
+                // Advance the last item which is not yet at the end of the join.
+                loop {
+                    let iter = self.iters.last_mut()?;
+                    if let Some(item) = iter.next() {
+                        *self.items.last_mut()? = item;
+                        break;
+                    }
+                    self.iters.pop();
+                    self.items.pop();
+                }
+            }
+
+            while self.items.len() < N {
+                let mut iter = self.iters.last().unwrap_or(&self.iter).clone();
+                match iter.next() {
+                    Some(item) => {
+                        self.iters.push(iter);
+                        self.items.push(item);
+                    }
+                    None => break,
+                }
+            }
+
+            if self.items.len() == N {
+                return Some(std::array::from_fn(|i| self.items[i].clone()));
+            }
+
+            // There are not enough items after the last one, and advancing
+            // it would only leave fewer, so the one before it is advanced.
+            self.iters.pop();
+            self.items.pop();
+            advance = true;
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! Associating each component type with the storage it should be stored in.
+use std::{
+    collections::btree_map,
+    iter::{Enumerate, FilterMap, Map},
+    ops::DerefMut,
+    slice,
+};
+
+use crate::{
+    default::DefaultStorage,
+    drain::{Drain, SparseDrain},
+    indexed::{IndexKey, IndexedRefMut, IndexedStorage, Unindex},
+    paged::{PagedDrain, PagedIter, PagedStorage},
+    shared::{SharedDrain, SharedIter, SharedRefMut, SharedStorage},
+    spatial::{GridRefMut, GridStorage, Position, RemoveFromCell},
+    Entity, Iter, Joinable, SparseStorage, Storage, WithEntities,
+};
+
+/// A type which can be stored in the storage `Self::Storage`.
+///
+/// Instead of implementing this trait manually, consider using the [`component!`] macro.
+///
+/// [`component!`]: ../macro.component.html
+pub trait Component: Sized {
+    /// The storage used for this component.
+    type Storage: ComponentStorage<Self>;
+}
+
+/// The storage used for components of type `T`.
+pub type StorageFor<T> = <T as Component>::Storage;
+
+/// Implements [`Component`] for a list of types.
+///
+/// Types are stored in a [`Storage`] by default,
+/// a different storage can be selected using `Type => StorageName`.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{component, component::StorageFor, Entity, SparseStorage};
+///
+/// struct Position(f32, f32);
+/// struct Velocity(f32, f32);
+/// struct Player;
+///
+/// component!(Position, Velocity, Player => SparseStorage);
+///
+/// let mut players: StorageFor<Player> = Default::default();
+/// players.insert(Entity(3), Player);
+/// let _: SparseStorage<Player> = players;
+/// ```
+///
+/// [`Component`]: component/trait.Component.html
+/// [`Storage`]: struct.Storage.html
+#[macro_export]
+macro_rules! component {
+    () => {};
+    ($ty:ty => $($storage:ident)::+ $(, $($rest:tt)*)?) => {
+        impl $crate::component::Component for $ty {
+            type Storage = $($storage)::+<$ty>;
+        }
+
+        $($crate::component!($($rest)*);)?
+    };
+    ($ty:ty $(, $($rest:tt)*)?) => {
+        impl $crate::component::Component for $ty {
+            type Storage = $crate::Storage<$ty>;
+        }
+
+        $($crate::component!($($rest)*);)?
+    };
+}
+
+/// The operations shared by all storages of components of type `T`.
+///
+/// The storages generated by [`soa_storage!`] do not implement this trait, as
+/// they never store a whole component which `get` could return a reference to.
+/// Neither does [`Archetypes`], which stores components of every type at once
+/// instead of being the storage of a single component type.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{component::ComponentStorage, Entity, SparseStorage, Storage};
+///
+/// fn copy<T: Clone, S: ComponentStorage<T>>(storage: &mut S, from: Entity, to: Entity) {
+///     if let Some(c) = storage.get(from).cloned() {
+///         storage.insert(to, c);
+///     }
+/// }
+///
+/// let mut names = Storage::new();
+/// names.insert(Entity(0), "Foo");
+/// copy(&mut names, Entity(0), Entity(1));
+/// assert_eq!(names.get(Entity(1)), Some(&"Foo"));
+///
+/// let mut sparse = SparseStorage::new();
+/// sparse.insert(Entity(7), 3);
+/// copy(&mut sparse, Entity(7), Entity(2));
+/// assert_eq!(ComponentStorage::len(&sparse), 2);
+/// ```
+///
+/// [`soa_storage!`]: ../macro.soa_storage.html
+/// [`Archetypes`]: ../archetype/struct.Archetypes.html
+pub trait ComponentStorage<T>: Default {
+    /// A mutable reference to a component in this storage.
+    type RefMut<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+    /// The iterator returned by `ComponentStorage::drain`.
+    type Drain<'a>: Joinable<Item = T>
+    where
+        Self: 'a;
+    /// The iterator returned by `ComponentStorage::iter`.
+    type Iter<'a>: Iterator<Item = (Entity, &'a T)>
+    where
+        Self: 'a,
+        T: 'a;
+
+    /// Creates an empty storage.
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all components in this storage.
+    fn clear(&mut self);
+
+    /// Returns the component of the entity at `idx` in case it exists.
+    fn get(&self, idx: Entity) -> Option<&T>;
+
+    fn get_mut(&mut self, idx: Entity) -> Option<Self::RefMut<'_>>;
+
+    /// Inserts a component for the entity at `idx`.
+    ///
+    /// In case the component was already present the previous
+    /// one is returned.
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T>;
+
+    /// Removes this component for the entity at `idx`.
+    fn remove(&mut self, idx: Entity) -> Option<T>;
+
+    /// Removes all components of this storage.
+    fn drain(&mut self) -> Self::Drain<'_>;
+
+    /// Returns `true` if the entity at `idx` has a component in this storage.
+    fn contains(&self, idx: Entity) -> bool {
+        self.get(idx).is_some()
+    }
+
+    /// Returns the number of components in this storage.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over all components in this storage, ordered by their entity.
+    fn iter(&self) -> Self::Iter<'_>;
+}
+
+type DenseIter<'a, T> = FilterMap<
+    Enumerate<slice::Iter<'a, Option<T>>>,
+    fn((usize, &'a Option<T>)) -> Option<(Entity, &'a T)>,
+>;
+
+fn dense_iter<T>(inner: &[Option<T>]) -> DenseIter<'_, T> {
+    inner
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| c.as_ref().map(|c| (Entity(i), c)))
+}
+
+impl<T> ComponentStorage<T> for Storage<T> {
+    type RefMut<'a>
+        = &'a mut T
+    where
+        T: 'a;
+    type Drain<'a>
+        = Drain<'a, T>
+    where
+        T: 'a;
+    type Iter<'a>
+        = DenseIter<'a, T>
+    where
+        T: 'a;
+
+    fn clear(&mut self) {
+        Storage::clear(self)
+    }
+
+    fn get(&self, idx: Entity) -> Option<&T> {
+        Storage::get(self, idx)
+    }
+
+    fn get_mut(&mut self, idx: Entity) -> Option<&mut T> {
+        Storage::get_mut(self, idx)
+    }
+
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        Storage::insert(self, idx, c)
+    }
+
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        Storage::remove(self, idx)
+    }
+
+    fn drain(&mut self) -> Drain<'_, T> {
+        Storage::drain(self)
+    }
+
+    fn len(&self) -> usize {
+        Storage::len(self)
+    }
+
+    fn iter(&self) -> DenseIter<'_, T> {
+        dense_iter(&self.inner)
+    }
+}
+
+type SparseEntries<'a, T> =
+    Map<btree_map::Iter<'a, usize, T>, fn((&'a usize, &'a T)) -> (Entity, &'a T)>;
+
+impl<T> ComponentStorage<T> for SparseStorage<T> {
+    type RefMut<'a>
+        = &'a mut T
+    where
+        T: 'a;
+    type Drain<'a>
+        = SparseDrain<'a, T>
+    where
+        T: 'a;
+    type Iter<'a>
+        = SparseEntries<'a, T>
+    where
+        T: 'a;
+
+    fn clear(&mut self) {
+        SparseStorage::clear(self)
+    }
+
+    fn get(&self, idx: Entity) -> Option<&T> {
+        SparseStorage::get(self, idx)
+    }
+
+    fn get_mut(&mut self, idx: Entity) -> Option<&mut T> {
+        SparseStorage::get_mut(self, idx)
+    }
+
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        SparseStorage::insert(self, idx, c)
+    }
+
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        SparseStorage::remove(self, idx)
+    }
+
+    fn drain(&mut self) -> SparseDrain<'_, T> {
+        SparseStorage::drain(self)
+    }
+
+    fn len(&self) -> usize {
+        SparseStorage::len(self)
+    }
+
+    fn iter(&self) -> SparseEntries<'_, T> {
+        self.inner.iter().map(|(&k, c)| (Entity(k), c))
+    }
+}
+
+impl<T, K: IndexKey<T>> ComponentStorage<T> for IndexedStorage<T, K> {
+    type RefMut<'a>
+        = IndexedRefMut<'a, T, K>
+    where
+        Self: 'a;
+    type Drain<'a>
+        = Drain<'a, T, Unindex<'a, K>>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = DenseIter<'a, T>
+    where
+        Self: 'a;
+
+    fn clear(&mut self) {
+        IndexedStorage::clear(self)
+    }
+
+    fn get(&self, idx: Entity) -> Option<&T> {
+        IndexedStorage::get(self, idx)
+    }
+
+    fn get_mut(&mut self, idx: Entity) -> Option<IndexedRefMut<'_, T, K>> {
+        IndexedStorage::get_mut(self, idx)
+    }
+
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        IndexedStorage::insert(self, idx, c)
+    }
+
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        IndexedStorage::remove(self, idx)
+    }
+
+    fn drain(&mut self) -> Drain<'_, T, Unindex<'_, K>> {
+        IndexedStorage::drain(self)
+    }
+
+    fn len(&self) -> usize {
+        self.storage().len()
+    }
+
+    fn iter(&self) -> DenseIter<'_, T> {
+        dense_iter(&self.storage().inner)
+    }
+}
+
+impl<T: Position> ComponentStorage<T> for GridStorage<T> {
+    type RefMut<'a>
+        = GridRefMut<'a, T>
+    where
+        T: 'a;
+    type Drain<'a>
+        = Drain<'a, T, RemoveFromCell<'a>>
+    where
+        T: 'a;
+    type Iter<'a>
+        = DenseIter<'a, T>
+    where
+        T: 'a;
+
+    fn clear(&mut self) {
+        GridStorage::clear(self)
+    }
+
+    fn get(&self, idx: Entity) -> Option<&T> {
+        GridStorage::get(self, idx)
+    }
+
+    fn get_mut(&mut self, idx: Entity) -> Option<GridRefMut<'_, T>> {
+        GridStorage::get_mut(self, idx)
+    }
+
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        GridStorage::insert(self, idx, c)
+    }
+
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        GridStorage::remove(self, idx)
+    }
+
+    fn drain(&mut self) -> Drain<'_, T, RemoveFromCell<'_>> {
+        GridStorage::drain(self)
+    }
+
+    fn len(&self) -> usize {
+        GridStorage::len(self)
+    }
+
+    fn iter(&self) -> DenseIter<'_, T> {
+        dense_iter(&self.storage().inner)
+    }
+}
+
+impl<T> ComponentStorage<T> for PagedStorage<T> {
+    type RefMut<'a>
+        = &'a mut T
+    where
+        T: 'a;
+    type Drain<'a>
+        = PagedDrain<'a, T>
+    where
+        T: 'a;
+    type Iter<'a>
+        = WithEntities<PagedIter<'a, T>>
+    where
+        T: 'a;
+
+    fn clear(&mut self) {
+        PagedStorage::clear(self)
+    }
+
+    fn get(&self, idx: Entity) -> Option<&T> {
+        PagedStorage::get(self, idx)
+    }
+
+    fn get_mut(&mut self, idx: Entity) -> Option<&mut T> {
+        PagedStorage::get_mut(self, idx)
+    }
+
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        PagedStorage::insert(self, idx, c)
+    }
+
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        PagedStorage::remove(self, idx)
+    }
+
+    fn drain(&mut self) -> PagedDrain<'_, T> {
+        PagedStorage::drain(self)
+    }
+
+    fn len(&self) -> usize {
+        PagedStorage::len(self)
+    }
+
+    fn iter(&self) -> WithEntities<PagedIter<'_, T>> {
+        self.join().with_entities()
+    }
+}
+
+impl<T: Ord + Clone> ComponentStorage<T> for SharedStorage<T> {
+    type RefMut<'a>
+        = SharedRefMut<'a, T>
+    where
+        T: 'a;
+    type Drain<'a>
+        = SharedDrain<'a, T>
+    where
+        T: 'a;
+    type Iter<'a>
+        = WithEntities<SharedIter<'a, T>>
+    where
+        T: 'a;
+
+    fn clear(&mut self) {
+        SharedStorage::clear(self)
+    }
+
+    fn get(&self, idx: Entity) -> Option<&T> {
+        SharedStorage::get(self, idx)
+    }
+
+    fn get_mut(&mut self, idx: Entity) -> Option<SharedRefMut<'_, T>> {
+        SharedStorage::get_mut(self, idx)
+    }
+
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        SharedStorage::insert(self, idx, c)
+    }
+
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        SharedStorage::remove(self, idx)
+    }
+
+    fn drain(&mut self) -> SharedDrain<'_, T> {
+        SharedStorage::drain(self)
+    }
+
+    fn len(&self) -> usize {
+        SharedStorage::len(self)
+    }
+
+    fn iter(&self) -> WithEntities<SharedIter<'_, T>> {
+        self.join().with_entities()
+    }
+}
+
+impl<T: Default> ComponentStorage<T> for DefaultStorage<T> {
+    type RefMut<'a>
+        = &'a mut T
+    where
+        T: 'a;
+    type Drain<'a>
+        = Drain<'a, T>
+    where
+        T: 'a;
+    type Iter<'a>
+        = WithEntities<Iter<'a, T>>
+    where
+        T: 'a;
+
+    fn clear(&mut self) {
+        DefaultStorage::clear(self)
+    }
+
+    fn get(&self, idx: Entity) -> Option<&T> {
+        self.get_override(idx)
+    }
+
+    fn get_mut(&mut self, idx: Entity) -> Option<&mut T> {
+        self.get_override_mut(idx)
+    }
+
+    fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        DefaultStorage::insert(self, idx, c)
+    }
+
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        DefaultStorage::remove(self, idx)
+    }
+
+    fn drain(&mut self) -> Drain<'_, T> {
+        DefaultStorage::drain(self)
+    }
+
+    fn len(&self) -> usize {
+        self.overrides()
+    }
+
+    fn iter(&self) -> WithEntities<Iter<'_, T>> {
+        self.iter_overrides()
+    }
+}
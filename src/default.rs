@@ -0,0 +1,204 @@
+//! A storage returning a default component for entities without one.
+use std::mem;
+
+use crate::{
+    drain::Drain, DoubleEndedJoin, Entity, Iter, Join, Joinable, Joined, Storage, WithEntities,
+};
+
+/// A storage which only stores components differing from a default,
+/// which is returned for all other entities.
+///
+/// When joined this behaves like [`Joinable::maybe`], except that the
+/// default is returned instead of `None`, so it never limits the join.
+/// Joining a `DefaultStorage` by itself therefore yields a component for every
+/// possible entity, never finishing when collected or iterated in reverse.
+/// It should be joined together with a storage which limits the join,
+/// use [`iter_overrides`] to only visit the overridden components.
+///
+/// As a [`ComponentStorage`], only the overrides are considered components,
+/// so `get` returns `None` for entities using the default.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{default::DefaultStorage, Entity, Joinable, Storage};
+///
+/// #[derive(Debug, Clone, PartialEq)]
+/// struct Tint(u8, u8, u8);
+///
+/// let mut tints = DefaultStorage::new(Tint(255, 255, 255));
+/// tints.insert(Entity(1), Tint(255, 0, 0));
+///
+/// let mut sprites = Storage::new();
+/// sprites.insert(Entity(0), "player.png");
+/// sprites.insert(Entity(1), "enemy.png");
+///
+/// let tinted: Vec<_> = (&sprites, &tints).join().collect();
+/// assert_eq!(
+///     tinted,
+///     vec![(&"player.png", &Tint(255, 255, 255)), (&"enemy.png", &Tint(255, 0, 0))]
+/// );
+///
+/// tints.get_mut(Entity(0)).0 = 0;
+/// assert_eq!(tints.overrides(), 2);
+/// ```
+///
+/// [`Joinable::maybe`]: ../trait.Joinable.html#method.maybe
+/// [`iter_overrides`]: struct.DefaultStorage.html#method.iter_overrides
+/// [`ComponentStorage`]: ../component/trait.ComponentStorage.html
+#[derive(Debug, Clone)]
+pub struct DefaultStorage<T> {
+    storage: Storage<T>,
+    default: T,
+}
+
+impl<T: Default> Default for DefaultStorage<T> {
+    fn default() -> Self {
+        DefaultStorage::new(T::default())
+    }
+}
+
+impl<T> DefaultStorage<T> {
+    /// Creates a new `DefaultStorage` returning `default` for all entities.
+    pub fn new(default: T) -> Self {
+        Self {
+            storage: Storage::new(),
+            default,
+        }
+    }
+
+    /// Returns the number of entities whose component differs from the default.
+    pub fn overrides(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// Returns the component used for entities without an override.
+    pub fn default_value(&self) -> &T {
+        &self.default
+    }
+
+    /// Replaces the component used for entities without an override.
+    pub fn set_default(&mut self, default: T) -> T {
+        mem::replace(&mut self.default, default)
+    }
+
+    /// Removes all overrides, resetting every entity to the default.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+    }
+
+    /// Returns the component of the entity at `idx`.
+    pub fn get(&self, idx: Entity) -> &T {
+        self.storage.get(idx).unwrap_or(&self.default)
+    }
+
+    /// Returns the component of the entity at `idx` in case it differs from the default.
+    pub fn get_override(&self, idx: Entity) -> Option<&T> {
+        self.storage.get(idx)
+    }
+
+    pub fn get_override_mut(&mut self, idx: Entity) -> Option<&mut T> {
+        self.storage.get_mut(idx)
+    }
+
+    /// Mutably accesses the component of the entity at `idx`,
+    /// overriding it with a copy of the default if necessary.
+    pub fn get_mut(&mut self, idx: Entity) -> &mut T
+    where
+        T: Clone,
+    {
+        if self.storage.get(idx).is_none() {
+            self.storage.insert(idx, self.default.clone());
+        }
+        self.storage.get_mut(idx).unwrap()
+    }
+
+    /// Overrides the component of the entity at `idx`.
+    ///
+    /// In case the component was already overridden the previous
+    /// one is returned.
+    pub fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        self.storage.insert(idx, c)
+    }
+
+    /// Resets the entity at `idx` to the default, returning its override.
+    pub fn remove(&mut self, idx: Entity) -> Option<T> {
+        self.storage.remove(idx)
+    }
+
+    /// Removes and yields all overrides, resetting every entity to the default.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        self.storage.drain()
+    }
+
+    /// Iterates over all overridden components, ordered by their entity.
+    pub fn iter_overrides(&self) -> WithEntities<Iter<'_, T>> {
+        (&self.storage).join().with_entities()
+    }
+}
+
+/// The iterator created by joining a `&DefaultStorage`.
+#[derive(Clone)]
+pub struct DefaultIter<'a, T> {
+    iter: Iter<'a, T>,
+    default: &'a T,
+}
+
+impl<'a, T> Iterator for DefaultIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        Some(self.iter.nth(n).unwrap_or(self.default))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for DefaultIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        Some(self.iter.nth_back(n).unwrap_or(self.default))
+    }
+}
+
+impl<'a, T> Join for DefaultIter<'a, T> {
+    fn may_skip(&mut self, _curr: usize) -> usize {
+        0
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.iter.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.iter.seek(n)
+    }
+}
+
+impl<'a, T> DoubleEndedJoin for DefaultIter<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        0
+    }
+}
+
+impl<'a, T> Joinable for &'a DefaultStorage<T> {
+    type Joined = DefaultIter<'a, T>;
+    type Item = &'a T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let iter = DefaultIter {
+            iter: (&self.storage).join().iter,
+            default: &self.default,
+        };
+        Joined::new(iter, usize::MAX)
+    }
+
+    fn fetch(self, entity: Entity) -> Option<&'a T> {
+        Some(self.get(entity))
+    }
+}
@@ -1,80 +1,346 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, mem};
 
-use crate::{Entity, Join, Joinable, Joined, SparseStorage, Storage};
+use crate::{
+    count_some, hooks::StorageHooks, Entity, ExactJoin, Join, Joinable, Joined, SparseStorage,
+    Storage, WithEntities,
+};
 
 impl<T> Storage<T> {
     /// Removes all component of this storage
-    pub fn drain(&mut self) -> Drain<T> {
-        Drain(self, 0)
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain {
+            storage: self,
+            position: 0,
+            ignored: 0,
+            on_remove: (),
+        }
+    }
+
+    /// Like `drain`, but calls `on_remove` for each removed component.
+    ///
+    /// Used by storages which keep additional state about their components,
+    /// so that this state stays in sync even if the drain is leaked.
+    pub(crate) fn drain_with<F: OnRemove<T>>(&mut self, on_remove: F) -> Drain<'_, T, F> {
+        Drain {
+            storage: self,
+            position: 0,
+            ignored: 0,
+            on_remove,
+        }
+    }
+
+    /// Only keeps the components for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(Entity, &mut T) -> bool) {
+        for (i, slot) in self.inner.iter_mut().enumerate() {
+            if let Some(c) = slot {
+                if !f(Entity(i), c) {
+                    let c = slot.take().unwrap();
+                    self.len -= 1;
+                    self.hooks.removed(Entity(i), &c);
+                }
+            }
+        }
+    }
+
+    /// Removes and yields the components for which `f` returns `true`.
+    ///
+    /// Components which are not visited, for example because
+    /// the iterator is dropped early, are kept. When joined with other storages,
+    /// components are only removed for entities which have all joined components.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entities, Entity, Joinable, Storage};
+    ///
+    /// let mut lifetimes = Storage::new();
+    /// lifetimes.insert(Entity(0), 3);
+    /// lifetimes.insert(Entity(1), 0);
+    /// lifetimes.insert(Entity(2), 0);
+    ///
+    /// let expired: Vec<_> = (lifetimes.drain_filter(|_, t| *t == 0), Entities).join().collect();
+    /// assert_eq!(expired, vec![(0, Entity(1)), (0, Entity(2))]);
+    /// assert_eq!(lifetimes.len(), 1);
+    /// ```
+    pub fn drain_filter<F>(&mut self, f: F) -> DrainFilter<'_, T, F>
+    where
+        F: FnMut(Entity, &mut T) -> bool,
+    {
+        DrainFilter {
+            storage: self,
+            position: 0,
+            filter: f,
+            checked: None,
+        }
     }
 }
 
 /// The iterator returned by `Storage::drain`.
 ///
-/// Using this struct in a `join` after mutating it
-/// can easily lead to unspecified, but not *unsound* behavior.
-pub struct Drain<'a, T>(&'a mut Storage<T>, usize);
+/// Like `Vec::drain`, all remaining components are removed once
+/// this is dropped, even if it was not fully consumed.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{Entity, Joinable, Storage};
+///
+/// let mut names = Storage::new();
+/// names.insert(Entity(1), "Foo");
+/// names.insert(Entity(3), "Bar");
+///
+/// let mut drain = names.drain().with_entities();
+/// assert_eq!(drain.next(), Some((Entity(1), "Foo")));
+/// drop(drain);
+/// assert!(names.is_empty());
+/// ```
+pub struct Drain<'a, T, F: OnRemove<T> = ()> {
+    storage: &'a mut Storage<T>,
+    position: usize,
+    /// The number of components which are removed without being yielded after `truncate`.
+    ignored: usize,
+    on_remove: F,
+}
 
-impl<'a, T> Join for Drain<'a, T> {
+impl<'a, T, F: OnRemove<T>> Drain<'a, T, F> {
+    fn remove(&mut self, idx: Entity) -> Option<T> {
+        let c = self.storage.remove(idx)?;
+        self.on_remove.removed(idx, &c);
+        Some(c)
+    }
+
+    /// Yields the `Entity` of each removed component alongside it.
+    ///
+    /// This is a shorthand for `self.join().with_entities()`.
+    pub fn with_entities(self) -> WithEntities<Self> {
+        self.join().with_entities()
+    }
+}
+
+impl<'a, T, F: OnRemove<T>> Drop for Drain<'a, T, F> {
+    fn drop(&mut self) {
+        for i in 0..self.storage.inner.len() {
+            self.remove(Entity(i));
+        }
+    }
+}
+
+impl<'a, T, F: OnRemove<T>> Join for Drain<'a, T, F> {
     fn may_skip(&mut self, curr: usize) -> usize {
-        self.0.inner[curr..]
-            .iter()
-            .take_while(|opt| opt.is_none())
-            .count()
+        self.storage.inner.get(curr..).map_or(0, |rest| {
+            rest.iter().take_while(|opt| opt.is_none()).count()
+        })
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        // All components before the current position have already been removed.
+        let len = self.storage.len() - self.ignored;
+        (len, Some(len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let end = self.position.saturating_add(len);
+        let ignored = self.storage.inner.get(end..).map_or(0, count_some);
+        self.ignored = self.ignored.max(ignored);
     }
 }
 
-impl<'a, T> Iterator for Drain<'a, T> {
+impl<'a, T, F: OnRemove<T>> ExactJoin for Drain<'a, T, F> {}
+
+impl<'a, T, F: OnRemove<T>> Iterator for Drain<'a, T, F> {
     type Item = T;
 
     fn next(&mut self) -> Option<T> {
-        let item = self.0.remove(Entity(self.1));
-        self.1 += 1;
+        let item = self.remove(Entity(self.position));
+        self.position += 1;
         item
     }
 
     fn nth(&mut self, n: usize) -> Option<T> {
-        for _ in 0..n {
-            self.0.remove(Entity(self.1));
-            self.1 += 1;
+        let end = self.position.saturating_add(n);
+        for i in self.position..end.min(self.storage.inner.len()) {
+            self.remove(Entity(i));
         }
+        self.position = end;
+        self.next()
+    }
+}
+
+impl<'a, T, F: OnRemove<T>> Joinable for Drain<'a, T, F> {
+    type Joined = Drain<'a, T, F>;
+    type Item = T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let (len, position) = (self.storage.inner.len(), self.position);
+        Joined::starting_at(self, position, len)
+    }
+}
+
+/// Updates the additional state a storage keeps about its components
+/// whenever a [`Drain`] removes one of them.
+///
+/// Storages without such state use `()`, which does nothing.
+///
+/// [`Drain`]: struct.Drain.html
+pub trait OnRemove<T> {
+    fn removed(&mut self, idx: Entity, c: &T);
+}
+
+impl<T> OnRemove<T> for () {
+    fn removed(&mut self, _idx: Entity, _c: &T) {}
+}
+
+/// The iterator returned by `Storage::drain_filter`.
+pub struct DrainFilter<'a, T, F> {
+    storage: &'a mut Storage<T>,
+    position: usize,
+    filter: F,
+    /// The position last checked by `removes_nth`, and whether its component matches.
+    checked: Option<(usize, bool)>,
+}
+
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> DrainFilter<'a, T, F> {
+    /// Returns whether the component of `idx` is removed, only calling the filter once.
+    fn matches(&mut self, idx: Entity) -> bool {
+        match self.checked.take() {
+            Some((position, matches)) if position == idx.0 => matches,
+            _ => match self.storage.inner.get_mut(idx.0).and_then(Option::as_mut) {
+                Some(c) => (self.filter)(idx, c),
+                None => false,
+            },
+        }
+    }
+}
+
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> Join for DrainFilter<'a, T, F> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.storage.inner.get(curr..).map_or(0, |rest| {
+            rest.iter().take_while(|opt| opt.is_none()).count()
+        })
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (0, Some(self.storage.len()))
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.position = self.position.saturating_add(n);
+    }
 
+    fn removes_nth(&mut self, n: usize) -> Option<bool> {
+        let idx = Entity(self.position.saturating_add(n));
+        let matches = self.matches(idx);
+        self.checked = Some((idx.0, matches));
+        Some(matches)
+    }
+}
+
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> Iterator for DrainFilter<'a, T, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let idx = Entity(self.position);
+        self.position += 1;
+        if self.matches(idx) {
+            self.storage.remove(idx)
+        } else {
+            None
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<T> {
+        self.position += n;
         self.next()
     }
 }
 
-impl<'a, T> Joinable for Drain<'a, T> {
-    type Joined = Drain<'a, T>;
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> Joinable for DrainFilter<'a, T, F> {
+    type Joined = Self;
     type Item = T;
 
     fn join(self) -> Joined<Self::Joined> {
-        let len = self.0.inner.len();
-        Joined::new(self, len)
+        let (len, position) = (self.storage.inner.len(), self.position);
+        Joined::starting_at(self, position, len)
+    }
+
+    fn fetch(mut self, entity: Entity) -> Option<T> {
+        // Jump straight to `entity`, as skipping with `nth` would filter the entity before it.
+        if entity.0 < self.position {
+            return None;
+        }
+        self.position = entity.0;
+        self.next()
     }
 }
 
 impl<T> SparseStorage<T> {
     /// Removes all component of this storage.
-    pub fn drain(&mut self) -> SparseDrain<T> {
+    pub fn drain(&mut self) -> SparseDrain<'_, T> {
         SparseDrain {
             inner: &mut self.inner,
+            hooks: &self.hooks,
+            position: 0,
+            ignored: 0,
+        }
+    }
+
+    /// Only keeps the components for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(Entity, &mut T) -> bool) {
+        let hooks = &self.hooks;
+        self.inner.retain(|&k, c| {
+            let keep = f(Entity(k), c);
+            if !keep {
+                hooks.removed(Entity(k), c);
+            }
+            keep
+        });
+    }
+
+    /// Removes and yields the components for which `f` returns `true`.
+    ///
+    /// See [`Storage::drain_filter`] for more details.
+    ///
+    /// [`Storage::drain_filter`]: ../struct.Storage.html#method.drain_filter
+    pub fn drain_filter<F>(&mut self, f: F) -> SparseDrainFilter<'_, T, F>
+    where
+        F: FnMut(Entity, &mut T) -> bool,
+    {
+        SparseDrainFilter {
+            inner: &mut self.inner,
+            hooks: &self.hooks,
             position: 0,
+            filter: f,
+            checked: None,
         }
     }
 }
 
 /// The iterator returned by `SparseStorage::drain`.
 ///
-/// Using this struct in a `join` after mutating it
-/// can easily lead to unspecified, but not *unsound* behavior.
+/// Like `Vec::drain`, all remaining components are removed once
+/// this is dropped, even if it was not fully consumed.
 pub struct SparseDrain<'a, T> {
     inner: &'a mut BTreeMap<usize, T>,
+    hooks: &'a StorageHooks<T>,
     position: usize,
+    /// The number of components which are removed without being yielded after `truncate`.
+    ignored: usize,
+}
+
+impl<'a, T> SparseDrain<'a, T> {
+    /// Yields the `Entity` of each removed component alongside it.
+    ///
+    /// This is a shorthand for `self.join().with_entities()`.
+    pub fn with_entities(self) -> WithEntities<Self> {
+        self.join().with_entities()
+    }
 }
 
 impl<'a, T> Drop for SparseDrain<'a, T> {
     fn drop(&mut self) {
-        self.inner.clear()
+        for (k, c) in mem::take(self.inner) {
+            self.hooks.removed(Entity(k), &c);
+        }
     }
 }
 
@@ -84,21 +350,41 @@ impl<'a, T> Join for SparseDrain<'a, T> {
         self.inner
             .range(self.position..)
             .next()
-            .map_or(std::usize::MAX, |(&k, _)| k - self.position)
+            .map_or(usize::MAX, |(&k, _)| k - self.position)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        // All components before the current position have already been removed.
+        let len = self.inner.len() - self.ignored;
+        (len, Some(len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let end = self.position.saturating_add(len);
+        self.ignored = self.ignored.max(self.inner.range(end..).count());
     }
 }
 
+impl<'a, T> ExactJoin for SparseDrain<'a, T> {}
+
 impl<'a, T> Iterator for SparseDrain<'a, T> {
     type Item = T;
 
     fn next(&mut self) -> Option<T> {
         let item = self.inner.remove(&self.position);
+        if let Some(ref c) = item {
+            self.hooks.removed(Entity(self.position), c);
+        }
         self.position += 1;
         item
     }
 
     fn nth(&mut self, n: usize) -> Option<T> {
-        self.position += n;
+        self.position = self.position.saturating_add(n);
+        let rest = self.inner.split_off(&self.position);
+        for (k, c) in mem::replace(self.inner, rest) {
+            self.hooks.removed(Entity(k), &c);
+        }
         self.next()
     }
 }
@@ -109,6 +395,96 @@ impl<'a, T> Joinable for SparseDrain<'a, T> {
 
     fn join(self) -> Joined<Self::Joined> {
         let len = self.inner.keys().last().copied().map_or(0, |v| v + 1);
-        Joined::new(self, len)
+        let position = self.position;
+        Joined::starting_at(self, position, len)
+    }
+}
+
+/// The iterator returned by `SparseStorage::drain_filter`.
+pub struct SparseDrainFilter<'a, T, F> {
+    inner: &'a mut BTreeMap<usize, T>,
+    hooks: &'a StorageHooks<T>,
+    position: usize,
+    filter: F,
+    /// The position last checked by `removes_nth`, and whether its component matches.
+    checked: Option<(usize, bool)>,
+}
+
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> SparseDrainFilter<'a, T, F> {
+    /// Returns whether the component of `idx` is removed, only calling the filter once.
+    fn matches(&mut self, idx: Entity) -> bool {
+        match self.checked.take() {
+            Some((position, matches)) if position == idx.0 => matches,
+            _ => match self.inner.get_mut(&idx.0) {
+                Some(c) => (self.filter)(idx, c),
+                None => false,
+            },
+        }
+    }
+}
+
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> Join for SparseDrainFilter<'a, T, F> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.position = curr;
+        self.inner
+            .range(self.position..)
+            .next()
+            .map_or(usize::MAX, |(&k, _)| k - self.position)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (0, Some(self.inner.len()))
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.position = self.position.saturating_add(n);
+    }
+
+    fn removes_nth(&mut self, n: usize) -> Option<bool> {
+        let idx = Entity(self.position.saturating_add(n));
+        let matches = self.matches(idx);
+        self.checked = Some((idx.0, matches));
+        Some(matches)
+    }
+}
+
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> Iterator for SparseDrainFilter<'a, T, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let idx = Entity(self.position);
+        self.position += 1;
+        if self.matches(idx) {
+            let c = self.inner.remove(&idx.0)?;
+            self.hooks.removed(idx, &c);
+            Some(c)
+        } else {
+            None
+        }
+    }
+
+    fn nth(&mut self, n: usize) -> Option<T> {
+        self.position += n;
+        self.next()
+    }
+}
+
+impl<'a, T, F: FnMut(Entity, &mut T) -> bool> Joinable for SparseDrainFilter<'a, T, F> {
+    type Joined = Self;
+    type Item = T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let len = self.inner.keys().last().copied().map_or(0, |v| v + 1);
+        let position = self.position;
+        Joined::starting_at(self, position, len)
+    }
+
+    fn fetch(mut self, entity: Entity) -> Option<T> {
+        // Jump straight to `entity`, as skipping with `nth` would filter the entity before it.
+        if entity.0 < self.position {
+            return None;
+        }
+        self.position = entity.0;
+        self.next()
     }
 }
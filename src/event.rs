@@ -0,0 +1,136 @@
+//! Broadcasting events between systems.
+use std::collections::{vec_deque, VecDeque};
+
+/// A cursor into an [`EventChannel`], created by [`EventChannel::register_reader`].
+///
+/// [`EventChannel`]: struct.EventChannel.html
+/// [`EventChannel::register_reader`]: struct.EventChannel.html#method.register_reader
+#[derive(Debug)]
+pub struct ReaderId {
+    next: usize,
+}
+
+/// A queue of events which can be read by multiple independent readers.
+///
+/// Each reader sees every event exactly once, as long as it reads
+/// the channel at least once per frame. Events are kept for two frames,
+/// meaning that events written during this frame are dropped during
+/// the second call to [`update`] after writing them.
+///
+/// Only up to `capacity` events are stored at once,
+/// in case more events are written, the oldest ones are dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{event::EventChannel, Entity};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Died(Entity);
+///
+/// let mut channel = EventChannel::new();
+/// let mut score = channel.register_reader();
+/// let mut sound = channel.register_reader();
+///
+/// channel.write(Died(Entity(3)));
+/// assert_eq!(channel.read(&mut score).collect::<Vec<_>>(), vec![&Died(Entity(3))]);
+/// assert_eq!(channel.read(&mut score).count(), 0);
+///
+/// channel.update();
+/// channel.write(Died(Entity(4)));
+/// assert_eq!(channel.read(&mut sound).count(), 2);
+/// ```
+///
+/// [`update`]: struct.EventChannel.html#method.update
+#[derive(Debug, Clone)]
+pub struct EventChannel<E> {
+    events: VecDeque<E>,
+    /// The id of the first stored event.
+    first: usize,
+    /// The id of the first event written during this frame.
+    frame_start: usize,
+    capacity: usize,
+}
+
+impl<E> Default for EventChannel<E> {
+    fn default() -> Self {
+        EventChannel::new()
+    }
+}
+
+impl<E> EventChannel<E> {
+    /// Creates a new `EventChannel` storing up to `1024` events at once.
+    pub fn new() -> Self {
+        Self::with_capacity(1024)
+    }
+
+    /// Creates a new `EventChannel` storing up to `capacity` events at once.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "an event channel requires a nonzero capacity");
+        Self {
+            events: VecDeque::new(),
+            first: 0,
+            frame_start: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the number of currently stored events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Creates a new reader, which only sees events written after its creation.
+    pub fn register_reader(&self) -> ReaderId {
+        ReaderId { next: self.end() }
+    }
+
+    /// Adds an event to this channel.
+    pub fn write(&mut self, event: E) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.first += 1;
+            self.frame_start = self.frame_start.max(self.first);
+        }
+
+        self.events.push_back(event);
+    }
+
+    /// Iterates over all events not yet seen by `reader`.
+    ///
+    /// Events which have already been dropped are skipped. Using a `reader`
+    /// registered with another channel does not panic, but may skip events.
+    pub fn read(&self, reader: &mut ReaderId) -> vec_deque::Iter<'_, E> {
+        let start = reader.next.clamp(self.first, self.end()) - self.first;
+        reader.next = self.end();
+        self.events.range(start..)
+    }
+
+    /// Starts a new frame, dropping all events written before the previous one.
+    pub fn update(&mut self) {
+        self.events.drain(..self.frame_start - self.first);
+        self.first = self.frame_start;
+        self.frame_start = self.end();
+    }
+
+    /// The id of the next written event.
+    fn end(&self) -> usize {
+        self.first + self.events.len()
+    }
+}
+
+impl<E> Extend<E> for EventChannel<E> {
+    fn extend<I: IntoIterator<Item = E>>(&mut self, iter: I) {
+        for event in iter {
+            self.write(event);
+        }
+    }
+}
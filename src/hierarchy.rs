@@ -0,0 +1,235 @@
+//! Parent child relationships between entities.
+//!
+//! The [`Parent`] and [`Children`] components of each entity are owned by a
+//! [`Hierarchy`], which keeps them consistent with each other.
+//!
+//! [`Parent`]: struct.Parent.html
+//! [`Children`]: struct.Children.html
+//! [`Hierarchy`]: struct.Hierarchy.html
+use crate::{component::ComponentStorage, set::EntitySet, Entity, Joinable, Joined, SparseStorage};
+
+/// The parent of an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Parent(pub Entity);
+
+/// The direct children of an entity, in the order they were added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Children(Vec<Entity>);
+
+impl Children {
+    pub fn as_slice(&self) -> &[Entity] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Stores the [`Parent`] and [`Children`] components of all entities.
+///
+/// Changing the parent of an entity automatically updates the children of
+/// both the previous and the new parent.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{hierarchy::Hierarchy, Entity, Joinable, Storage};
+///
+/// let (root, a, b, c) = (Entity(0), Entity(1), Entity(2), Entity(3));
+///
+/// let mut hierarchy = Hierarchy::new();
+/// hierarchy.set_parent(b, root);
+/// hierarchy.set_parent(a, root);
+/// hierarchy.set_parent(c, b);
+///
+/// let order: Vec<_> = hierarchy.depth_first(root).collect();
+/// assert_eq!(order, vec![root, b, c, a]);
+///
+/// let mut names = Storage::new();
+/// names.insert(a, "a");
+/// names.insert(c, "c");
+/// for (name, entity) in (&names, hierarchy.depth_first(b)).join() {
+///     assert_eq!((*name, entity), ("c", c));
+/// }
+///
+/// let removed = hierarchy.despawn_recursive(b);
+/// removed.remove_from(&mut names);
+/// assert_eq!(removed.as_slice(), &[b, c]);
+/// assert_eq!(hierarchy.children(root), &[a]);
+/// assert_eq!(names.get(c), None);
+/// ```
+///
+/// [`Parent`]: struct.Parent.html
+/// [`Children`]: struct.Children.html
+#[derive(Debug, Clone, Default)]
+pub struct Hierarchy {
+    parents: SparseStorage<Parent>,
+    children: SparseStorage<Children>,
+}
+
+impl Hierarchy {
+    /// Creates a new `Hierarchy` without any relationships.
+    pub fn new() -> Self {
+        Self {
+            parents: SparseStorage::new(),
+            children: SparseStorage::new(),
+        }
+    }
+
+    /// Returns the parent of `entity` in case it has one.
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.parents.get(entity).map(|p| p.0)
+    }
+
+    /// Returns the direct children of `entity`.
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        self.children.get(entity).map_or(&[], Children::as_slice)
+    }
+
+    /// The [`Parent`] components of all entities, which can be used in a `join`.
+    ///
+    /// [`Parent`]: struct.Parent.html
+    pub fn parent_storage(&self) -> &SparseStorage<Parent> {
+        &self.parents
+    }
+
+    /// The [`Children`] components of all entities, which can be used in a `join`.
+    ///
+    /// [`Children`]: struct.Children.html
+    pub fn children_storage(&self) -> &SparseStorage<Children> {
+        &self.children
+    }
+
+    /// Returns `true` if `ancestor` is either `entity` itself or one
+    /// of its transitive parents.
+    pub fn is_ancestor(&self, ancestor: Entity, mut entity: Entity) -> bool {
+        loop {
+            if entity == ancestor {
+                return true;
+            } else if let Some(parent) = self.parent(entity) {
+                entity = parent;
+            } else {
+                return false;
+            }
+        }
+    }
+
+    /// Sets the parent of `child`, returning the previous one.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `child` is an ancestor of `parent`,
+    /// as this would create a cycle.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Option<Entity> {
+        assert!(
+            !self.is_ancestor(child, parent),
+            "setting the parent of {:?} to {:?} would create a cycle",
+            child,
+            parent
+        );
+
+        let previous = self.remove_parent(child);
+        self.parents.insert(child, Parent(parent));
+        if let Some(children) = self.children.get_mut(parent) {
+            children.0.push(child);
+        } else {
+            self.children.insert(parent, Children(vec![child]));
+        }
+        previous
+    }
+
+    /// Detaches `child` from its parent, returning the previous parent.
+    pub fn remove_parent(&mut self, child: Entity) -> Option<Entity> {
+        let Parent(parent) = self.parents.remove(child)?;
+        let children = self
+            .children
+            .get_mut(parent)
+            .expect("parent without children");
+        children.0.retain(|&c| c != child);
+        if children.is_empty() {
+            self.children.remove(parent);
+        }
+        Some(parent)
+    }
+
+    /// Iterates over `root` and all of its descendants in depth-first order.
+    pub fn depth_first(&self, root: Entity) -> DepthFirst<'_> {
+        DepthFirst {
+            hierarchy: self,
+            stack: vec![root],
+        }
+    }
+
+    /// Removes `root` and all of its descendants from this hierarchy.
+    ///
+    /// Their components in other storages are removed using [`Despawned::remove_from`].
+    ///
+    /// [`Despawned::remove_from`]: struct.Despawned.html#method.remove_from
+    pub fn despawn_recursive(&mut self, root: Entity) -> Despawned {
+        self.remove_parent(root);
+        let removed: Vec<Entity> = self.depth_first(root).collect();
+        for &entity in removed.iter() {
+            self.parents.remove(entity);
+            self.children.remove(entity);
+        }
+        Despawned(removed)
+    }
+}
+
+/// The entities removed by [`Hierarchy::despawn_recursive`], in depth-first order.
+///
+/// [`Hierarchy::despawn_recursive`]: struct.Hierarchy.html#method.despawn_recursive
+#[must_use = "the despawned entities still have their components in other storages"]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Despawned(Vec<Entity>);
+
+impl Despawned {
+    pub fn as_slice(&self) -> &[Entity] {
+        &self.0
+    }
+
+    /// Removes the components of all despawned entities from `storage`.
+    pub fn remove_from<T, S: ComponentStorage<T>>(&self, storage: &mut S) -> &Self {
+        for &entity in self.0.iter() {
+            storage.remove(entity);
+        }
+        self
+    }
+}
+
+/// The iterator returned by [`Hierarchy::depth_first`].
+///
+/// When used in a `join`, the entities are yielded ordered by their
+/// index, just like in every other join.
+///
+/// [`Hierarchy::depth_first`]: struct.Hierarchy.html#method.depth_first
+#[derive(Debug, Clone)]
+pub struct DepthFirst<'a> {
+    hierarchy: &'a Hierarchy,
+    stack: Vec<Entity>,
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let entity = self.stack.pop()?;
+        self.stack
+            .extend(self.hierarchy.children(entity).iter().rev().copied());
+        Some(entity)
+    }
+}
+
+impl<'a> Joinable for DepthFirst<'a> {
+    type Joined = <EntitySet as Joinable>::Joined;
+    type Item = Entity;
+
+    fn join(self) -> Joined<Self::Joined> {
+        self.collect::<EntitySet>().join()
+    }
+}
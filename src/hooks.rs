@@ -0,0 +1,175 @@
+//! Callbacks run whenever components are added to or removed from a storage.
+use std::{fmt, sync::Arc};
+
+use crate::Entity;
+
+type Hook<T> = Arc<dyn Fn(Entity, &T) + Send + Sync>;
+type ReplaceHook<T> = Arc<dyn Fn(Entity, &T, &T) + Send + Sync>;
+
+/// The lifecycle hooks of a storage, accessible using `hooks_mut`.
+///
+/// Hooks are also run when removing components using `clear` or `drain`,
+/// and `on_remove` is run for all remaining components when the storage is dropped.
+/// Cloning a storage shares its hooks with the clone.
+///
+/// As hooks receive references to the components, storages are invariant
+/// over their component type, so a `Storage<&'static str>` can not be used
+/// where a `Storage<&'a str>` is expected.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::{Arc, Mutex};
+/// use crow_ecs::{Entity, Joinable, Storage};
+///
+/// let released = Arc::new(Mutex::new(Vec::new()));
+///
+/// let mut textures = Storage::new();
+/// let r = Arc::clone(&released);
+/// textures
+///     .hooks_mut()
+///     .on_remove(move |_, &texture: &u32| r.lock().unwrap().push(texture));
+///
+/// textures.insert(Entity(0), 7);
+/// textures.insert(Entity(1), 8);
+/// textures.remove(Entity(0));
+/// textures.drain().join().for_each(drop);
+/// assert_eq!(*released.lock().unwrap(), vec![7, 8]);
+/// ```
+pub struct Hooks<T> {
+    on_insert: Option<Hook<T>>,
+    on_replace: Option<ReplaceHook<T>>,
+    on_remove: Option<Hook<T>>,
+}
+
+impl<T> Default for Hooks<T> {
+    fn default() -> Self {
+        Hooks::new()
+    }
+}
+
+impl<T> Clone for Hooks<T> {
+    fn clone(&self) -> Self {
+        Hooks {
+            on_insert: self.on_insert.clone(),
+            on_replace: self.on_replace.clone(),
+            on_remove: self.on_remove.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Hooks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_insert", &self.on_insert.is_some())
+            .field("on_replace", &self.on_replace.is_some())
+            .field("on_remove", &self.on_remove.is_some())
+            .finish()
+    }
+}
+
+impl<T> Hooks<T> {
+    pub fn new() -> Self {
+        Hooks {
+            on_insert: None,
+            on_replace: None,
+            on_remove: None,
+        }
+    }
+
+    /// Sets the function called after a component was added
+    /// to an entity which previously did not have one.
+    pub fn on_insert(&mut self, f: impl Fn(Entity, &T) + Send + Sync + 'static) -> &mut Self {
+        self.on_insert = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the function called after the component of an entity
+    /// was replaced, with the previous and the new component.
+    pub fn on_replace(&mut self, f: impl Fn(Entity, &T, &T) + Send + Sync + 'static) -> &mut Self {
+        self.on_replace = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the function called after a component was removed.
+    pub fn on_remove(&mut self, f: impl Fn(Entity, &T) + Send + Sync + 'static) -> &mut Self {
+        self.on_remove = Some(Arc::new(f));
+        self
+    }
+
+    /// Removes all hooks.
+    pub fn clear(&mut self) {
+        *self = Hooks::new();
+    }
+
+    pub(crate) fn inserted(&self, entity: Entity, c: &T) {
+        if let Some(ref f) = self.on_insert {
+            f(entity, c)
+        }
+    }
+
+    pub(crate) fn replaced(&self, entity: Entity, previous: &T, c: &T) {
+        if let Some(ref f) = self.on_replace {
+            f(entity, previous, c)
+        }
+    }
+
+    pub(crate) fn removed(&self, entity: Entity, c: &T) {
+        if let Some(ref f) = self.on_remove {
+            f(entity, c)
+        }
+    }
+}
+
+/// The hooks of a storage, which are only allocated once they are accessed.
+pub(crate) struct StorageHooks<T>(Option<Box<Hooks<T>>>);
+
+impl<T> Clone for StorageHooks<T> {
+    fn clone(&self) -> Self {
+        StorageHooks(self.0.clone())
+    }
+}
+
+impl<T> fmt::Debug for StorageHooks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(ref hooks) => hooks.fmt(f),
+            None => Hooks::<T>::new().fmt(f),
+        }
+    }
+}
+
+impl<T> StorageHooks<T> {
+    pub(crate) fn new() -> Self {
+        StorageHooks(None)
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut Hooks<T> {
+        self.0.get_or_insert_with(Default::default)
+    }
+
+    /// Returns `true` if removing components runs a hook.
+    pub(crate) fn on_remove(&self) -> bool {
+        self.0
+            .as_ref()
+            .is_some_and(|hooks| hooks.on_remove.is_some())
+    }
+
+    pub(crate) fn inserted(&self, entity: Entity, c: &T) {
+        if let Some(ref hooks) = self.0 {
+            hooks.inserted(entity, c)
+        }
+    }
+
+    pub(crate) fn replaced(&self, entity: Entity, previous: &T, c: &T) {
+        if let Some(ref hooks) = self.0 {
+            hooks.replaced(entity, previous, c)
+        }
+    }
+
+    pub(crate) fn removed(&self, entity: Entity, c: &T) {
+        if let Some(ref hooks) = self.0 {
+            hooks.removed(entity, c)
+        }
+    }
+}
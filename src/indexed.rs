@@ -0,0 +1,232 @@
+//! A storage with a secondary index on its component values.
+use std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    ops::{Deref, DerefMut, RangeBounds},
+};
+
+use crate::{
+    drain::{Drain, OnRemove},
+    set::EntitySet,
+    Entity, Iter, Joinable, Joined, Storage,
+};
+
+/// The key used by an [`IndexedStorage`] to look up its components.
+///
+/// Every `T: Ord + Clone` can be used to index itself.
+///
+/// [`IndexedStorage`]: struct.IndexedStorage.html
+pub trait IndexKey<T>: Ord + Clone {
+    /// Computes the key of the given component.
+    fn of(component: &T) -> Self;
+}
+
+impl<T: Ord + Clone> IndexKey<T> for T {
+    fn of(component: &T) -> T {
+        component.clone()
+    }
+}
+
+static EMPTY: EntitySet = EntitySet::new();
+
+/// A storage which can efficiently find all entities whose component has a given key.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{indexed::{IndexKey, IndexedStorage}, Entity, Joinable, Storage};
+///
+/// struct Name(String);
+///
+/// impl IndexKey<Name> for String {
+///     fn of(name: &Name) -> String {
+///         name.0.clone()
+///     }
+/// }
+///
+/// let mut names = IndexedStorage::<Name, String>::new();
+/// names.insert(Entity(0), Name("player".to_string()));
+/// names.insert(Entity(3), Name("boss".to_string()));
+///
+/// let mut health = Storage::new();
+/// health.insert(Entity(3), 100);
+///
+/// for (health, boss) in (&mut health, names.find("boss")).join() {
+///     assert_eq!(boss, Entity(3));
+///     *health -= 10;
+/// }
+///
+/// names.get_mut(Entity(3)).unwrap().0 = "defeated".to_string();
+/// assert!(names.find("boss").is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct IndexedStorage<T, K = T> {
+    storage: Storage<T>,
+    index: BTreeMap<K, EntitySet>,
+}
+
+impl<T, K: IndexKey<T>> Default for IndexedStorage<T, K> {
+    fn default() -> Self {
+        IndexedStorage::new()
+    }
+}
+
+impl<T, K: IndexKey<T>> IndexedStorage<T, K> {
+    /// Creates a new `IndexedStorage`.
+    pub fn new() -> Self {
+        Self {
+            storage: Storage::new(),
+            index: BTreeMap::new(),
+        }
+    }
+
+    /// Removes all components in this storage.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+        self.index.clear();
+    }
+
+    /// Returns the component of the entity at `idx` in case it exists.
+    pub fn get(&self, idx: Entity) -> Option<&T> {
+        self.storage.get(idx)
+    }
+
+    /// Mutably accesses the component of the entity at `idx`.
+    ///
+    /// The index is updated once the returned guard is dropped.
+    pub fn get_mut(&mut self, idx: Entity) -> Option<IndexedRefMut<'_, T, K>> {
+        let component = self.storage.get_mut(idx)?;
+        Some(IndexedRefMut {
+            entity: idx,
+            key: K::of(component),
+            component,
+            index: &mut self.index,
+        })
+    }
+
+    /// Inserts a component for the entity at `idx`.
+    ///
+    /// In case the component was already present the previous
+    /// one is returned.
+    pub fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        let key = K::of(&c);
+        let previous = self.storage.insert(idx, c);
+        if let Some(ref previous) = previous {
+            unindex(&mut self.index, idx, &K::of(previous));
+        }
+        self.index.entry(key).or_default().insert(idx);
+        previous
+    }
+
+    /// Removes this component for the entity at `idx`.
+    pub fn remove(&mut self, idx: Entity) -> Option<T> {
+        let c = self.storage.remove(idx)?;
+        unindex(&mut self.index, idx, &K::of(&c));
+        Some(c)
+    }
+
+    /// Removes all components of this storage.
+    pub fn drain(&mut self) -> Drain<'_, T, Unindex<'_, K>> {
+        self.storage.drain_with(Unindex(&mut self.index))
+    }
+
+    /// Returns all entities whose component has the given key.
+    pub fn find<Q>(&self, key: &Q) -> &EntitySet
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.index.get(key).unwrap_or(&EMPTY)
+    }
+
+    /// Returns all entities whose component has a key inside of `range`.
+    pub fn range<Q, R>(&self, range: R) -> EntitySet
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.index
+            .range::<Q, R>(range)
+            .flat_map(|(_, entities)| entities.iter())
+            .collect()
+    }
+
+    /// Iterates over all distinct keys in ascending order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.index.keys()
+    }
+
+    pub(crate) fn storage(&self) -> &Storage<T> {
+        &self.storage
+    }
+}
+
+/// Removes the components drained from an [`IndexedStorage`] from its index.
+///
+/// [`IndexedStorage`]: struct.IndexedStorage.html
+#[derive(Debug)]
+pub struct Unindex<'a, K>(&'a mut BTreeMap<K, EntitySet>);
+
+impl<'a, T, K: IndexKey<T>> OnRemove<T> for Unindex<'a, K> {
+    fn removed(&mut self, idx: Entity, c: &T) {
+        unindex(self.0, idx, &K::of(c))
+    }
+}
+
+fn unindex<K: Ord>(index: &mut BTreeMap<K, EntitySet>, idx: Entity, key: &K) {
+    let entities = index.get_mut(key).expect("missing index entry");
+    entities.remove(idx);
+    if entities.is_empty() {
+        index.remove(key);
+    }
+}
+
+/// A mutable reference to a component of an [`IndexedStorage`].
+///
+/// The index is updated once this guard is dropped.
+///
+/// [`IndexedStorage`]: struct.IndexedStorage.html
+pub struct IndexedRefMut<'a, T, K: IndexKey<T>> {
+    entity: Entity,
+    key: K,
+    component: &'a mut T,
+    index: &'a mut BTreeMap<K, EntitySet>,
+}
+
+impl<'a, T, K: IndexKey<T>> Deref for IndexedRefMut<'a, T, K> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.component
+    }
+}
+
+impl<'a, T, K: IndexKey<T>> DerefMut for IndexedRefMut<'a, T, K> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.component
+    }
+}
+
+impl<'a, T, K: IndexKey<T>> Drop for IndexedRefMut<'a, T, K> {
+    fn drop(&mut self) {
+        let key = K::of(self.component);
+        if key != self.key {
+            unindex(self.index, self.entity, &self.key);
+            self.index.entry(key).or_default().insert(self.entity);
+        }
+    }
+}
+
+impl<'a, T, K> Joinable for &'a IndexedStorage<T, K> {
+    type Joined = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        (&self.storage).join()
+    }
+
+    fn fetch(self, entity: Entity) -> Option<&'a T> {
+        self.storage.get(entity)
+    }
+}
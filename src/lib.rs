@@ -3,19 +3,37 @@
 
 use std::{
     collections::{btree_map, BTreeMap},
-    iter::{self, Peekable},
     mem,
-    ops::RangeFrom,
+    ops::{Bound, Range, RangeBounds},
     slice,
 };
 
 mod tuple;
 
+pub mod archetype;
+pub mod chunk;
+pub mod combinations;
+pub mod component;
+pub mod default;
 pub mod drain;
+pub mod event;
+pub mod hierarchy;
+pub mod hooks;
+pub mod indexed;
 pub mod maybe;
 pub mod not;
-
+pub mod paged;
+pub mod relation;
+pub mod set;
+pub mod shared;
+pub mod soa;
+pub mod spatial;
+pub mod subset;
+
+use combinations::Combinations;
+use hooks::{Hooks, StorageHooks};
 use maybe::Maybe;
+use subset::Subset;
 
 /// An entity usable as in index into storages.
 ///
@@ -34,6 +52,9 @@ pub struct Entity(pub usize);
 #[derive(Debug, Clone)]
 pub struct Storage<T> {
     inner: Vec<Option<T>>,
+    hooks: StorageHooks<T>,
+    /// The number of components in `inner`.
+    len: usize,
 }
 
 impl<T> Default for Storage<T> {
@@ -42,9 +63,36 @@ impl<T> Default for Storage<T> {
     }
 }
 
+impl<T> Drop for Storage<T> {
+    fn drop(&mut self) {
+        // Only visit the remaining components if a hook wants them.
+        if self.hooks.on_remove() {
+            self.clear();
+        }
+    }
+}
+
 impl<T> Storage<T> {
     pub fn new() -> Self {
-        Self { inner: Vec::new() }
+        Self {
+            inner: Vec::new(),
+            hooks: StorageHooks::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of components in this storage.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the lifecycle hooks of this storage.
+    pub fn hooks_mut(&mut self) -> &mut Hooks<T> {
+        self.hooks.get_mut()
     }
 
     /// Removes all components in this storage.
@@ -52,8 +100,54 @@ impl<T> Storage<T> {
         // We don't just clear `inner`, as we
         // don't want to resize the vector the next time
         // a component is inserted.
-        for c in self.inner.iter_mut() {
-            *c = None;
+        for (i, c) in self.inner.iter_mut().enumerate() {
+            if let Some(c) = c.take() {
+                self.len -= 1;
+                self.hooks.removed(Entity(i), &c);
+            }
+        }
+    }
+
+    /// Reserves space for at least `additional` more entities after
+    /// the ones this storage currently has slots for.
+    ///
+    /// Slots are kept when components are removed, so this may
+    /// start after the largest entity with a component.
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional)
+    }
+
+    /// Frees all memory after the component of the largest entity.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entity, Storage};
+    ///
+    /// let mut storage = Storage::new();
+    /// storage.insert(Entity(3), 0u32);
+    /// storage.insert(Entity(1_000), 1);
+    /// storage.remove(Entity(1_000));
+    ///
+    /// storage.shrink_to_fit();
+    /// assert!(storage.memory_usage().capacity < 1_000);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        let len = self
+            .inner
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1);
+        self.inner.truncate(len);
+        self.inner.shrink_to_fit();
+    }
+
+    /// Returns the amount of memory used by this storage.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            len: self.len,
+            capacity: self.inner.capacity(),
+            bytes: self.inner.capacity() * mem::size_of::<Option<T>>(),
         }
     }
 
@@ -74,6 +168,47 @@ impl<T> Storage<T> {
         }
     }
 
+    /// Mutably accesses the components of multiple different entities at once.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entity, GetManyError, Storage};
+    ///
+    /// let mut velocity = Storage::new();
+    /// velocity.insert(Entity(0), 3);
+    /// velocity.insert(Entity(4), -2);
+    ///
+    /// let [a, b] = velocity.get_many_mut([Entity(0), Entity(4)]).unwrap();
+    /// std::mem::swap(a, b);
+    /// assert_eq!(velocity.get(Entity(0)), Some(&-2));
+    ///
+    /// assert_eq!(
+    ///     velocity.get_many_mut([Entity(4), Entity(4)]).err(),
+    ///     Some(GetManyError::Duplicate(Entity(4)))
+    /// );
+    /// ```
+    pub fn get_many_mut<const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> Result<[&mut T; N], GetManyError> {
+        let mut found: [Option<&mut T>; N] = std::array::from_fn(|_| None);
+        let mut rest = &mut self.inner[..];
+        let mut offset = 0;
+        for (id, i) in sorted_ids(&entities)? {
+            if id >= offset + rest.len() {
+                break;
+            }
+
+            let (start, end) = mem::take(&mut rest).split_at_mut(id - offset + 1);
+            found[i] = start.last_mut().and_then(Option::as_mut);
+            rest = end;
+            offset = id + 1;
+        }
+
+        all_found(found, &entities)
+    }
+
     /// Inserts a component for the entity at `idx`.
     ///
     /// In case the component was already present the previous
@@ -83,22 +218,41 @@ impl<T> Storage<T> {
             self.inner.resize_with(idx.0 + 1, || None);
         }
 
-        mem::replace(&mut self.inner[idx.0], Some(c))
+        let previous = self.inner[idx.0].replace(c);
+        let c = self.inner[idx.0].as_ref().unwrap();
+        if let Some(ref previous) = previous {
+            self.hooks.replaced(idx, previous, c);
+        } else {
+            self.len += 1;
+            self.hooks.inserted(idx, c);
+        }
+        previous
     }
 
     /// Removes this component for the entity at `idx`.
     pub fn remove(&mut self, idx: Entity) -> Option<T> {
-        self.inner.get_mut(idx.0).map(Option::take).flatten()
+        let c = self.inner.get_mut(idx.0).and_then(Option::take)?;
+        self.len -= 1;
+        self.hooks.removed(idx, &c);
+        Some(c)
     }
 }
 
 pub struct Iter<'a, T> {
     slice: &'a [Option<T>],
+    /// The number of components in `slice`.
+    len: usize,
+    /// The number of remaining positions, which may exceed `slice.len()`.
+    positions: usize,
 }
 
 impl<'a, T> Clone for Iter<'a, T> {
     fn clone(&self) -> Self {
-        Iter { slice: self.slice }
+        Iter {
+            slice: self.slice,
+            len: self.len,
+            positions: self.positions,
+        }
     }
 }
 
@@ -106,6 +260,38 @@ impl<'a, T> Join for Iter<'a, T> {
     fn may_skip(&mut self, _curr: usize) -> usize {
         self.slice.iter().take_while(|opt| opt.is_none()).count()
     }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        if len < self.slice.len() {
+            self.slice = &self.slice[..len];
+        }
+        // Recounted, as `seek` only keeps an upper bound in `len`.
+        self.len = count_some(self.slice);
+        self.positions = self.positions.min(len);
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.positions = self.positions.saturating_sub(n);
+        self.slice = self.slice.get(n..).unwrap_or(&[]);
+    }
+}
+
+impl<'a, T> ExactJoin for Iter<'a, T> {}
+
+impl<'a, T> DoubleEndedJoin for Iter<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        self.positions - self.slice.len()
+            + self
+                .slice
+                .iter()
+                .rev()
+                .take_while(|opt| opt.is_none())
+                .count()
+    }
 }
 
 impl<'a, T> Iterator for Iter<'a, T> {
@@ -116,13 +302,35 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 
     fn nth(&mut self, n: usize) -> Option<&'a T> {
+        self.positions = self.positions.saturating_sub(n.saturating_add(1));
         if self.slice.len() > n {
             let (start, end) = self.slice.split_at(n + 1);
             self.slice = end;
+            self.len -= count_some(start);
             start.last().unwrap().as_ref()
         } else {
             self.slice = &[];
+            self.len = 0;
+            None
+        }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        if n >= self.positions {
+            self.truncate(0);
             None
+        } else {
+            let target = self.positions - 1 - n;
+            self.truncate(target + 1);
+            let item = self.slice.last().filter(|_| target < self.slice.len());
+            self.truncate(target);
+            item.and_then(Option::as_ref)
         }
     }
 }
@@ -132,23 +340,81 @@ impl<'a, T> Joinable for &'a Storage<T> {
     type Item = &'a T;
 
     fn join(self) -> Joined<Self::Joined> {
-        Joined::new(Iter { slice: &self.inner }, self.inner.len())
+        Joined::new(
+            Iter {
+                slice: &self.inner,
+                len: self.len,
+                positions: usize::MAX,
+            },
+            self.inner.len(),
+        )
+    }
+
+    fn fetch(self, entity: Entity) -> Option<&'a T> {
+        self.get(entity)
     }
 }
 
+pub(crate) fn count_some<T>(slice: &[Option<T>]) -> usize {
+    slice.iter().filter(|opt| opt.is_some()).count()
+}
+
 pub struct IterMut<'a, T> {
     iter: slice::IterMut<'a, Option<T>>,
+    /// The number of components in `iter`.
+    len: usize,
+    /// The number of remaining positions, which may exceed `iter.len()`.
+    positions: usize,
+}
+
+impl<'a, T> IterMut<'a, T> {
+    /// Splits the remaining slice at `mid`, keeping the first half.
+    fn split_off(&mut self, mid: usize) -> &'a mut [Option<T>] {
+        let slice = mem::replace(&mut self.iter, [].iter_mut()).into_slice();
+        let (start, end) = slice.split_at_mut(mid.min(slice.len()));
+        self.len -= count_some(end);
+        self.iter = start.iter_mut();
+        end
+    }
 }
 
 impl<'a, T> Iterator for IterMut<'a, T> {
     type Item = &'a mut T;
 
     fn next(&mut self) -> Option<&'a mut T> {
-        self.iter.next().map(Option::as_mut).flatten()
+        self.positions = self.positions.saturating_sub(1);
+        let item = self.iter.next().and_then(Option::as_mut);
+        if item.is_some() {
+            self.len -= 1;
+        }
+        item
     }
 
     fn nth(&mut self, n: usize) -> Option<&'a mut T> {
-        self.iter.nth(n).map(Option::as_mut).flatten()
+        self.positions = self.positions.saturating_sub(n);
+        let slice = mem::replace(&mut self.iter, [].iter_mut()).into_slice();
+        let (skipped, rest) = slice.split_at_mut(n.min(slice.len()));
+        self.len -= count_some(skipped);
+        self.iter = rest.iter_mut();
+        self.next()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<&'a mut T> {
+        if n >= self.positions {
+            self.truncate(0);
+            None
+        } else {
+            let target = self.positions - 1 - n;
+            self.truncate(target + 1);
+            self.positions = target;
+            self.split_off(target).first_mut().and_then(Option::as_mut)
+        }
     }
 }
 
@@ -159,6 +425,33 @@ impl<'a, T> Join for IterMut<'a, T> {
         self.iter = slice.iter_mut();
         next
     }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.split_off(len);
+        // Recounted, as `seek` only keeps an upper bound in `len`.
+        self.len = count_some(self.iter.as_slice());
+        self.positions = self.positions.min(len);
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.positions = self.positions.saturating_sub(n);
+        if n > 0 {
+            self.iter.nth(n - 1);
+        }
+    }
+}
+
+impl<'a, T> ExactJoin for IterMut<'a, T> {}
+
+impl<'a, T> DoubleEndedJoin for IterMut<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        let slice = self.iter.as_slice();
+        self.positions - slice.len() + slice.iter().rev().take_while(|opt| opt.is_none()).count()
+    }
 }
 
 impl<'a, T> Joinable for &'a mut Storage<T> {
@@ -170,10 +463,16 @@ impl<'a, T> Joinable for &'a mut Storage<T> {
         Joined::new(
             IterMut {
                 iter: self.inner.iter_mut(),
+                len: self.len,
+                positions: usize::MAX,
             },
             len,
         )
     }
+
+    fn fetch(self, entity: Entity) -> Option<&'a mut T> {
+        self.get_mut(entity)
+    }
 }
 
 /// A storage which can be used if the given component is not associated with
@@ -181,6 +480,7 @@ impl<'a, T> Joinable for &'a mut Storage<T> {
 #[derive(Debug, Clone)]
 pub struct SparseStorage<T> {
     inner: BTreeMap<usize, T>,
+    hooks: StorageHooks<T>,
 }
 
 impl<T> Default for SparseStorage<T> {
@@ -189,17 +489,66 @@ impl<T> Default for SparseStorage<T> {
     }
 }
 
+impl<T> Drop for SparseStorage<T> {
+    fn drop(&mut self) {
+        // Only visit the remaining components if a hook wants them.
+        if self.hooks.on_remove() {
+            self.clear();
+        }
+    }
+}
+
 impl<T> SparseStorage<T> {
     /// Creates a new `SparseStorage`.
     pub fn new() -> Self {
         Self {
             inner: BTreeMap::new(),
+            hooks: StorageHooks::new(),
         }
     }
 
+    /// Returns the number of components in this storage.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns the lifecycle hooks of this storage.
+    pub fn hooks_mut(&mut self) -> &mut Hooks<T> {
+        self.hooks.get_mut()
+    }
+
     /// Removes all components in this storage.
     pub fn clear(&mut self) {
-        self.inner.clear()
+        for (k, c) in mem::take(&mut self.inner) {
+            self.hooks.removed(Entity(k), &c);
+        }
+    }
+
+    /// Does nothing, as each component is allocated separately.
+    ///
+    /// This exists to be interchangeable with [`Storage::reserve`].
+    ///
+    /// [`Storage::reserve`]: struct.Storage.html#method.reserve
+    pub fn reserve(&mut self, _additional: usize) {}
+
+    /// Does nothing, as no memory is kept after removing a component.
+    ///
+    /// This exists to be interchangeable with [`Storage::shrink_to_fit`].
+    ///
+    /// [`Storage::shrink_to_fit`]: struct.Storage.html#method.shrink_to_fit
+    pub fn shrink_to_fit(&mut self) {}
+
+    /// Returns an estimate of the amount of memory used by this storage.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            len: self.inner.len(),
+            capacity: self.inner.len(),
+            bytes: self.inner.len() * mem::size_of::<(usize, T)>(),
+        }
     }
 
     /// Returns the component of the entity at `idx` in case it exists.
@@ -211,23 +560,72 @@ impl<T> SparseStorage<T> {
         self.inner.get_mut(&idx.0)
     }
 
+    /// Mutably accesses the components of multiple different entities at once.
+    ///
+    /// This takes time proportional to the number of components
+    /// between the smallest and the largest entity.
+    pub fn get_many_mut<const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> Result<[&mut T; N], GetManyError> {
+        let mut found: [Option<&mut T>; N] = std::array::from_fn(|_| None);
+        let ids = sorted_ids(&entities)?;
+        if let (Some(&(first, _)), Some(&(last, _))) = (ids.first(), ids.last()) {
+            let mut range = self.inner.range_mut(first..=last).peekable();
+            for (id, i) in ids {
+                while range.next_if(|&(&k, _)| k < id).is_some() {}
+                found[i] = range.next_if(|&(&k, _)| k == id).map(|(_, c)| c);
+            }
+        }
+
+        all_found(found, &entities)
+    }
+
     /// Inserts a component for the entity at `idx`.
     ///
     /// In case the component was already present the previous
     /// one is returned.
     pub fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
-        self.inner.insert(idx.0, c)
+        let previous = self.inner.insert(idx.0, c);
+        let c = &self.inner[&idx.0];
+        if let Some(ref previous) = previous {
+            self.hooks.replaced(idx, previous, c);
+        } else {
+            self.hooks.inserted(idx, c);
+        }
+        previous
     }
 
     /// Removes this component for the entity at `idx`.
     pub fn remove(&mut self, idx: Entity) -> Option<T> {
-        self.inner.remove(&idx.0)
+        let c = self.inner.remove(&idx.0)?;
+        self.hooks.removed(idx, &c);
+        Some(c)
     }
 }
 
 pub struct SparseIter<'a, T> {
     inner: &'a BTreeMap<usize, T>,
     position: usize,
+    /// The position after the last remaining one.
+    end: usize,
+    /// The number of components between `position` and `end`.
+    len: usize,
+}
+
+impl<'a, T> SparseIter<'a, T> {
+    pub(crate) fn new(inner: &'a BTreeMap<usize, T>) -> Self {
+        SparseIter {
+            inner,
+            position: 0,
+            end: usize::MAX,
+            len: inner.len(),
+        }
+    }
+
+    fn range(&self) -> btree_map::Range<'a, usize, T> {
+        self.inner.range(self.position..self.end.max(self.position))
+    }
 }
 
 impl<'a, T> Clone for SparseIter<'a, T> {
@@ -235,6 +633,8 @@ impl<'a, T> Clone for SparseIter<'a, T> {
         SparseIter {
             inner: self.inner,
             position: self.position,
+            end: self.end,
+            len: self.len,
         }
     }
 }
@@ -242,10 +642,34 @@ impl<'a, T> Clone for SparseIter<'a, T> {
 impl<'a, T> Join for SparseIter<'a, T> {
     fn may_skip(&mut self, curr: usize) -> usize {
         self.position = curr;
-        self.inner
-            .range(self.position..)
+        self.range()
             .next()
-            .map_or(std::usize::MAX, |(&k, _)| k - self.position)
+            .map_or(usize::MAX, |(&k, _)| k - self.position)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        // Recounted, as `seek` only keeps an upper bound in `len`.
+        self.end = self.end.min(self.position.saturating_add(len));
+        self.len = self.range().count();
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.position = self.position.saturating_add(n);
+    }
+}
+
+impl<'a, T> ExactJoin for SparseIter<'a, T> {}
+
+impl<'a, T> DoubleEndedJoin for SparseIter<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        let remaining = self.end.saturating_sub(self.position);
+        self.range()
+            .next_back()
+            .map_or(remaining, |(&k, _)| self.end - 1 - k)
     }
 }
 
@@ -253,43 +677,131 @@ impl<'a, T> Iterator for SparseIter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<&'a T> {
-        let item = self.inner.get(&self.position);
+        let item = if self.position < self.end {
+            self.inner.get(&self.position)
+        } else {
+            None
+        };
+        if item.is_some() {
+            self.len -= 1;
+        }
         self.position += 1;
         item
     }
 
     fn nth(&mut self, n: usize) -> Option<&'a T> {
-        self.position += n;
+        let end = self.position.saturating_add(n);
+        self.len -= self
+            .inner
+            .range(self.position..end.min(self.end).max(self.position))
+            .count();
+        self.position = end;
         self.next()
     }
 }
 
+impl<'a, T> DoubleEndedIterator for SparseIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        let remaining = self.end.saturating_sub(self.position);
+        if n >= remaining {
+            self.truncate(0);
+            None
+        } else {
+            self.truncate(remaining - n);
+            let item = self.inner.get(&(self.end - 1));
+            self.truncate(remaining - n - 1);
+            item
+        }
+    }
+}
+
 impl<'a, T> Joinable for &'a SparseStorage<T> {
     type Joined = SparseIter<'a, T>;
     type Item = &'a T;
 
     fn join(self) -> Joined<Self::Joined> {
         Joined::new(
-            SparseIter {
-                inner: &self.inner,
-                position: 0,
-            },
+            SparseIter::new(&self.inner),
             self.inner.keys().last().copied().map_or(0, |v| v + 1),
         )
     }
+
+    fn fetch(self, entity: Entity) -> Option<&'a T> {
+        self.get(entity)
+    }
 }
 
 pub struct SparseIterMut<'a, T> {
-    inner: Peekable<btree_map::IterMut<'a, usize, T>>,
+    inner: btree_map::IterMut<'a, usize, T>,
+    /// The next entry of `inner`, in case it has already been taken.
+    front: Option<(usize, &'a mut T)>,
+    /// The last entry of `inner`, in case it has already been taken.
+    back: Option<(usize, &'a mut T)>,
     position: usize,
+    /// The position after the last remaining one.
+    end: usize,
+}
+
+impl<'a, T> SparseIterMut<'a, T> {
+    fn peek_front(&mut self) -> Option<usize> {
+        if self.front.is_none() {
+            self.front = match self.inner.next() {
+                Some((&k, v)) => Some((k, v)),
+                None => self.back.take(),
+            };
+        }
+
+        self.front.as_ref().map(|&(k, _)| k)
+    }
+
+    fn peek_back(&mut self) -> Option<usize> {
+        if self.back.is_none() {
+            self.back = match self.inner.next_back() {
+                Some((&k, v)) => Some((k, v)),
+                None => self.front.take(),
+            };
+        }
+
+        self.back.as_ref().map(|&(k, _)| k)
+    }
 }
 
 impl<'a, T> Join for SparseIterMut<'a, T> {
     fn may_skip(&mut self, curr: usize) -> usize {
         self.position = curr;
-        self.inner
-            .peek()
-            .map_or(std::usize::MAX, |&(&k, _)| k - curr)
+        while self.peek_front().is_some_and(|k| k < curr) {
+            self.front = None;
+        }
+
+        self.peek_front().map_or(usize::MAX, |k| k - curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        let len =
+            self.inner.len() + usize::from(self.front.is_some()) + usize::from(self.back.is_some());
+        (len, Some(len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.end = self.end.min(self.position.saturating_add(len));
+        while self.peek_back().is_some_and(|k| k >= self.end) {
+            self.back = None;
+        }
+    }
+}
+
+impl<'a, T> ExactJoin for SparseIterMut<'a, T> {}
+
+impl<'a, T> DoubleEndedJoin for SparseIterMut<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        match self.peek_back() {
+            Some(k) if k >= self.position => self.end - 1 - k,
+            _ => self.end.saturating_sub(self.position),
+        }
     }
 }
 
@@ -298,17 +810,16 @@ impl<'a, T> Iterator for SparseIterMut<'a, T> {
 
     fn next(&mut self) -> Option<&'a mut T> {
         let position = self.position;
-        while self.inner.peek().map_or(false, |&(&k, _)| k < position) {
-            self.inner.next();
+        while self.peek_front().is_some_and(|k| k < position) {
+            self.front = None;
         }
 
-        let item = if self.inner.peek().map_or(false, |&(&k, _)| k == position) {
-            self.inner.next().map(|(_, v)| v)
+        self.position += 1;
+        if position < self.end && self.peek_front() == Some(position) {
+            self.front.take().map(|(_, v)| v)
         } else {
             None
-        };
-        self.position += 1;
-        item
+        }
     }
 
     fn nth(&mut self, n: usize) -> Option<&'a mut T> {
@@ -317,6 +828,30 @@ impl<'a, T> Iterator for SparseIterMut<'a, T> {
     }
 }
 
+impl<'a, T> DoubleEndedIterator for SparseIterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<&'a mut T> {
+        let remaining = self.end.saturating_sub(self.position);
+        if n >= remaining {
+            self.truncate(0);
+            None
+        } else {
+            let target = self.end - 1 - n;
+            self.truncate(target + 1 - self.position);
+            let item = if self.peek_back() == Some(target) {
+                self.back.take().map(|(_, v)| v)
+            } else {
+                None
+            };
+            self.truncate(target - self.position);
+            item
+        }
+    }
+}
+
 impl<'a, T> Joinable for &'a mut SparseStorage<T> {
     type Joined = SparseIterMut<'a, T>;
     type Item = &'a mut T;
@@ -325,12 +860,19 @@ impl<'a, T> Joinable for &'a mut SparseStorage<T> {
         let len = self.inner.keys().last().copied().map_or(0, |v| v + 1);
         Joined::new(
             SparseIterMut {
-                inner: self.inner.iter_mut().peekable(),
+                inner: self.inner.iter_mut(),
+                front: None,
+                back: None,
                 position: 0,
+                end: usize::MAX,
             },
             len,
         )
     }
+
+    fn fetch(self, entity: Entity) -> Option<&'a mut T> {
+        self.get_mut(entity)
+    }
 }
 
 /// A joinable struct returning the currently iterated `Entity`.
@@ -342,17 +884,27 @@ pub struct Entities;
 ///
 /// [`Entities::join`]: struct.Entities.html
 #[derive(Debug, Clone)]
-pub struct EntitiesIter(iter::Map<RangeFrom<usize>, fn(usize) -> Entity>);
+pub struct EntitiesIter(Range<usize>);
 
 impl Iterator for EntitiesIter {
     type Item = Entity;
 
     fn next(&mut self) -> Option<Entity> {
-        self.0.next()
+        self.0.next().map(Entity)
     }
 
     fn nth(&mut self, n: usize) -> Option<Entity> {
-        self.0.nth(n)
+        self.0.nth(n).map(Entity)
+    }
+}
+
+impl DoubleEndedIterator for EntitiesIter {
+    fn next_back(&mut self) -> Option<Entity> {
+        self.0.next_back().map(Entity)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Entity> {
+        self.0.nth_back(n).map(Entity)
     }
 }
 
@@ -360,6 +912,16 @@ impl Join for EntitiesIter {
     fn may_skip(&mut self, _curr: usize) -> usize {
         0
     }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.end = self.0.end.min(self.0.start.saturating_add(len));
+    }
+}
+
+impl DoubleEndedJoin for EntitiesIter {
+    fn may_skip_back(&mut self) -> usize {
+        0
+    }
 }
 
 impl Joinable for Entities {
@@ -367,10 +929,11 @@ impl Joinable for Entities {
     type Item = Entity;
 
     fn join(self) -> Joined<Self::Joined> {
-        Joined::new(
-            EntitiesIter((0..).map(Entity as fn(usize) -> Entity)),
-            std::usize::MAX,
-        )
+        Joined::new(EntitiesIter(0..usize::MAX), usize::MAX)
+    }
+
+    fn fetch(self, entity: Entity) -> Option<Entity> {
+        Some(entity)
     }
 }
 
@@ -388,6 +951,48 @@ pub enum UniqueError {
     Multiple,
 }
 
+/// The memory used by a storage, returned by `memory_usage`.
+///
+/// This does not include memory owned by the components themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The number of stored components.
+    pub len: usize,
+    /// The number of entities which fit into the allocated memory.
+    pub capacity: usize,
+    /// The number of allocated bytes.
+    pub bytes: usize,
+}
+
+/// The error returned by `get_many_mut`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GetManyError {
+    /// The entity does not have a component in this storage.
+    Missing(Entity),
+    /// The entity was requested more than once.
+    Duplicate(Entity),
+}
+
+/// Returns the ids of `entities` in ascending order, together with their index.
+fn sorted_ids<const N: usize>(entities: &[Entity; N]) -> Result<[(usize, usize); N], GetManyError> {
+    let mut ids = std::array::from_fn(|i| (entities[i].0, i));
+    ids.sort_unstable();
+    match ids.windows(2).find(|w| w[0].0 == w[1].0) {
+        Some(w) => Err(GetManyError::Duplicate(Entity(w[0].0))),
+        None => Ok(ids),
+    }
+}
+
+fn all_found<'a, T, const N: usize>(
+    found: [Option<&'a mut T>; N],
+    entities: &[Entity; N],
+) -> Result<[&'a mut T; N], GetManyError> {
+    match found.iter().position(Option::is_none) {
+        Some(i) => Err(GetManyError::Missing(entities[i])),
+        None => Ok(found.map(Option::unwrap)),
+    }
+}
+
 impl<T: Iterator + Join> Joined<T> {
     /// Returns the only entity with the given components.
     ///
@@ -408,6 +1013,30 @@ impl<T: Iterator + Join> Joined<T> {
     pub fn new(iter: T, len: usize) -> Self {
         Self { iter, len, pos: 0 }
     }
+
+    /// Creates a `Joined` for an `iter` which already skipped
+    /// the positions before `pos`.
+    pub(crate) fn starting_at(iter: T, pos: usize, len: usize) -> Self {
+        Self { iter, len, pos }
+    }
+
+    /// Yields the `Entity` of each item alongside it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entity, Joinable, Storage};
+    ///
+    /// let mut names = Storage::new();
+    /// names.insert(Entity(3), "Foo");
+    /// names.insert(Entity(5), "Bar");
+    ///
+    /// let named: Vec<_> = (&names).join().with_entities().collect();
+    /// assert_eq!(named, vec![(Entity(3), &"Foo"), (Entity(5), &"Bar")]);
+    /// ```
+    pub fn with_entities(self) -> WithEntities<T> {
+        WithEntities(self)
+    }
 }
 
 impl<T: Join + Iterator> Iterator for Joined<T> {
@@ -416,6 +1045,11 @@ impl<T: Join + Iterator> Iterator for Joined<T> {
     fn next(&mut self) -> Option<T::Item> {
         while self.pos < self.len {
             let nth = self.iter.may_skip(self.pos);
+            if nth >= self.len - self.pos {
+                self.pos = self.len;
+                return None;
+            }
+
             self.pos += nth;
             if let Some(item) = self.iter.nth(nth) {
                 self.pos += 1;
@@ -427,14 +1061,133 @@ impl<T: Join + Iterator> Iterator for Joined<T> {
 
         None
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let positions = self.len.saturating_sub(self.pos);
+        let (lower, upper) = self.iter.remaining();
+        (
+            lower.min(positions),
+            Some(upper.map_or(positions, |upper| upper.min(positions))),
+        )
+    }
+
+    fn count(self) -> usize {
+        match self.size_hint() {
+            (lower, Some(upper)) if lower == upper => lower,
+            _ => self.fold(0, |count, _| count + 1),
+        }
+    }
 }
 
-/// A trait used to optimize iterators created by `<T as Joinable>::join()`.
-pub trait Join {
-    fn may_skip(&mut self, curr: usize) -> usize;
+impl<T: DoubleEndedJoin> DoubleEndedIterator for Joined<T> {
+    fn next_back(&mut self) -> Option<T::Item> {
+        if self.pos < self.len {
+            self.iter.truncate(self.len - self.pos);
+        }
+
+        while self.pos < self.len {
+            let nth = self.iter.may_skip_back();
+            if nth >= self.len - self.pos {
+                self.len = self.pos;
+                return None;
+            }
+
+            self.len -= nth + 1;
+            if let Some(item) = self.iter.nth_back(nth) {
+                return Some(item);
+            }
+        }
+
+        None
+    }
 }
 
-/// Join multiple storages for easy iteration.
+impl<T: ExactJoin + Iterator> ExactSizeIterator for Joined<T> {}
+
+/// The iterator returned by [`Joined::with_entities`].
+///
+/// [`Joined::with_entities`]: struct.Joined.html#method.with_entities
+#[derive(Debug, Clone)]
+pub struct WithEntities<T>(Joined<T>);
+
+impl<T: Join + Iterator> Iterator for WithEntities<T> {
+    type Item = (Entity, T::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.0.next()?;
+        Some((Entity(self.0.pos - 1), item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T: DoubleEndedJoin> DoubleEndedIterator for WithEntities<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self.0.next_back()?;
+        Some((Entity(self.0.len), item))
+    }
+}
+
+impl<T: ExactJoin + Iterator> ExactSizeIterator for WithEntities<T> {}
+
+/// A trait used to optimize iterators created by `<T as Joinable>::join()`.
+pub trait Join {
+    fn may_skip(&mut self, curr: usize) -> usize;
+
+    /// Returns the bounds on the number of items this iterator yields
+    /// if it is not joined with anything else.
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+
+    /// Ignores all but the next `len` positions.
+    ///
+    /// This has to be implemented by all `ExactJoin`s, as their `remaining`
+    /// must not include the ignored positions and has to be exact again after `seek`.
+    fn truncate(&mut self, _len: usize) {}
+
+    /// Skips the next `n` positions without yielding their items.
+    ///
+    /// Unlike `Iterator::nth`, this only has to keep an upper bound
+    /// in `remaining` until the next `truncate`, so storages can
+    /// jump to the target position directly.
+    fn seek(&mut self, n: usize)
+    where
+        Self: Iterator,
+    {
+        if n > 0 {
+            self.nth(n - 1);
+        }
+    }
+
+    /// For joins which remove the items they yield, returns whether `nth(n)`
+    /// would yield an item, without removing it. Returns `None` for all other joins.
+    ///
+    /// This lets tuples only remove items once all of their members yield one.
+    fn removes_nth(&mut self, _n: usize) -> Option<bool> {
+        None
+    }
+}
+
+/// A `Join` whose `remaining` returns the exact number of remaining items.
+pub trait ExactJoin: Join {}
+
+/// A `Join` which can also be iterated from the back.
+///
+/// `nth_back` counts positions, not items, and returns `None`
+/// for positions without a component.
+pub trait DoubleEndedJoin: Join + DoubleEndedIterator {
+    /// Returns the number of positions at the back which
+    /// are known to not contain a component.
+    fn may_skip_back(&mut self) -> usize;
+}
+
+/// Join multiple storages for easy iteration.
+///
+/// Tuples of up to 16 joinables are joinable themselves. As tuples can be
+/// nested, e.g. `((&a, &b), (&c, &d))`, joins are not limited to this width.
 ///
 /// # Examples
 ///
@@ -467,6 +1220,117 @@ pub trait Joinable: Sized {
     fn maybe(self) -> Maybe<Self::Joined> {
         Maybe::new(self.join().iter)
     }
+
+    /// Only joins the entities in `range`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entities, Entity, Joinable, Storage};
+    ///
+    /// let mut health = Storage::new();
+    /// for i in 0..10 {
+    ///     health.insert(Entity(i), 100);
+    /// }
+    ///
+    /// let chunk: Vec<_> = (&health, Entities).join_range(4..6).collect();
+    /// assert_eq!(chunk, vec![(&100, Entity(4)), (&100, Entity(5))]);
+    /// ```
+    fn join_range<R: RangeBounds<usize>>(self, range: R) -> Joined<Self::Joined> {
+        let mut joined = self.join();
+        joined.len = match range.end_bound() {
+            Bound::Included(&end) => joined.len.min(end.saturating_add(1)),
+            Bound::Excluded(&end) => joined.len.min(end),
+            Bound::Unbounded => joined.len,
+        };
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+
+        if start >= joined.len {
+            joined.pos = joined.len;
+        } else if start > joined.pos {
+            joined.iter.seek(start - joined.pos);
+            joined.pos = start;
+        }
+        joined.iter.truncate(joined.len.saturating_sub(joined.pos));
+        joined
+    }
+
+    /// Only joins the given `entities`.
+    ///
+    /// The items are returned in ascending order of their entity,
+    /// each entity is only visited once, even if it is selected multiple times.
+    /// Each selected entity is looked up directly in the joined storages,
+    /// so the entities between them are not visited.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entity, Joinable, Storage};
+    ///
+    /// let mut health = Storage::new();
+    /// let mut armor = Storage::new();
+    /// for i in 0..10 {
+    ///     health.insert(Entity(i), 100);
+    /// }
+    /// armor.insert(Entity(7), 5);
+    ///
+    /// let selected = [Entity(7), Entity(2)];
+    /// for health in (&mut health).join_entities(&selected) {
+    ///     *health -= 10;
+    /// }
+    /// assert_eq!(health.get(Entity(7)), Some(&90));
+    ///
+    /// let armored: Vec<_> = (&health, &armor).join_entities(&selected).collect();
+    /// assert_eq!(armored, vec![(&90, &5)]);
+    /// ```
+    fn join_entities(self, entities: &[Entity]) -> Joined<Subset<Self::Joined>> {
+        let joined = self.join();
+        let subset = Subset::new(joined.iter, joined.pos, entities);
+        let len = joined.len.min(subset.join_len());
+        Joined::starting_at(subset, joined.pos, len)
+    }
+
+    /// Returns the item of `entity`, in case it would be yielded by `join`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use crow_ecs::{Entity, Joinable, SparseStorage, Storage};
+    ///
+    /// let mut position = Storage::new();
+    /// let mut velocity = Storage::new();
+    /// let mut frozen = SparseStorage::new();
+    /// for i in 0..3 {
+    ///     position.insert(Entity(i), 0);
+    ///     velocity.insert(Entity(i), 1);
+    /// }
+    /// frozen.insert(Entity(1), ());
+    ///
+    /// if let Some((p, v, ())) = (&mut position, &velocity, !&frozen).fetch(Entity(0)) {
+    ///     *p += v;
+    /// }
+    /// assert_eq!(position.get(Entity(0)), Some(&1));
+    /// assert_eq!((&position, &velocity, !&frozen).fetch(Entity(1)), None);
+    /// ```
+    fn fetch(self, entity: Entity) -> Option<Self::Item> {
+        self.join_range(entity.0..=entity.0).next()
+    }
+
+    /// Returns all combinations of `N` different joined entities.
+    ///
+    /// See [`Combinations`] for more details.
+    ///
+    /// [`Combinations`]: combinations/struct.Combinations.html
+    fn iter_combinations<const N: usize>(self) -> Combinations<WithEntities<Self::Joined>, N>
+    where
+        Self::Joined: Clone,
+    {
+        Combinations::new(self.join().with_entities())
+    }
 }
 
 impl<'a, T> Joinable for &'a &T
@@ -479,6 +1343,10 @@ where
     fn join(self) -> Joined<Self::Joined> {
         <&T>::join(self)
     }
+
+    fn fetch(self, entity: Entity) -> Option<Self::Item> {
+        <&T>::fetch(self, entity)
+    }
 }
 
 impl<'a, T> Joinable for &'a &mut T
@@ -491,6 +1359,10 @@ where
     fn join(self) -> Joined<Self::Joined> {
         <&T>::join(self)
     }
+
+    fn fetch(self, entity: Entity) -> Option<Self::Item> {
+        <&T>::fetch(self, entity)
+    }
 }
 
 impl<'a, T> Joinable for &'a mut &mut T
@@ -503,6 +1375,10 @@ where
     fn join(self) -> Joined<Self::Joined> {
         <&'a mut T>::join(self)
     }
+
+    fn fetch(self, entity: Entity) -> Option<Self::Item> {
+        <&'a mut T>::fetch(self, entity)
+    }
 }
 
 #[cfg(test)]
@@ -708,4 +1584,1206 @@ mod tests {
         assert_eq!(iter.next(), Some(&17));
         assert_eq!(iter.iter.may_skip(1), 3);
     }
+
+    #[test]
+    fn hierarchy() {
+        use hierarchy::Hierarchy;
+
+        let (a, b, c, d) = (Entity(0), Entity(1), Entity(2), Entity(3));
+        let mut h = Hierarchy::new();
+        h.set_parent(b, a);
+        h.set_parent(c, b);
+        h.set_parent(d, a);
+        assert_eq!(h.set_parent(c, d), Some(b));
+        assert!(h.children(b).is_empty());
+        assert_eq!(h.children(d), &[c]);
+        assert!(h.is_ancestor(a, c));
+
+        let mut s = Storage::new();
+        s.insert(b, 1);
+        s.insert(c, 2);
+        let joined: Vec<_> = (&s, h.depth_first(d)).join().collect();
+        assert_eq!(joined, vec![(&2, c)]);
+
+        let mut sparse = SparseStorage::new();
+        sparse.insert(d, ());
+        sparse.insert(Entity(4), ());
+        let despawned = h.despawn_recursive(a);
+        assert_eq!(despawned.as_slice(), &[a, b, d, c]);
+        despawned.remove_from(&mut s).remove_from(&mut sparse);
+        assert!(s.is_empty());
+        assert_eq!((&sparse, Entities).join().count(), 1);
+        assert_eq!((h.parent_storage(), Entities).join().count(), 0);
+        assert_eq!((h.children_storage(), Entities).join().count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn hierarchy_cycle() {
+        let mut h = hierarchy::Hierarchy::new();
+        h.set_parent(Entity(1), Entity(0));
+        h.set_parent(Entity(0), Entity(1));
+    }
+
+    #[test]
+    fn relation() {
+        use relation::Relation;
+
+        let (a, b, c) = (Entity(0), Entity(1), Entity(2));
+        let mut r = Relation::new();
+        assert_eq!(r.insert(a, b, 1), None);
+        assert_eq!(r.insert(a, b, 2), Some(1));
+        r.insert(a, c, 3);
+        r.insert(c, b, 4);
+        assert_eq!(r.len(), 3);
+        assert_eq!(r.targets(a).collect::<Vec<_>>(), vec![(b, &2), (c, &3)]);
+        assert_eq!(r.sources(b).collect::<Vec<_>>(), vec![a, c]);
+
+        let mut s = Storage::new();
+        s.insert(a, "a");
+        s.insert(b, "b");
+        let forward: Vec<_> = (&s, r.forward(), Entities)
+            .join()
+            .map(|(_, t, e)| (e, t.count()))
+            .collect();
+        assert_eq!(forward, vec![(a, 2)]);
+
+        r.remove_entity(c);
+        assert_eq!(r.sources(b).collect::<Vec<_>>(), vec![a]);
+        assert_eq!(r.remove(a, b), Some(2));
+        assert!(r.is_empty());
+        assert_eq!((r.reverse(), Entities).join().count(), 0);
+    }
+
+    #[test]
+    fn indexed() {
+        use indexed::IndexedStorage;
+
+        let mut s = IndexedStorage::<u32>::new();
+        s.insert(Entity(0), 1);
+        s.insert(Entity(2), 2);
+        s.insert(Entity(5), 2);
+        assert_eq!(s.insert(Entity(0), 3), Some(1));
+        assert_eq!(s.insert(Entity(2), 2), Some(2));
+        assert!(s.find(&1).is_empty());
+        assert_eq!(
+            s.find(&2).iter().collect::<Vec<_>>(),
+            vec![Entity(2), Entity(5)]
+        );
+
+        *s.get_mut(Entity(5)).unwrap() = 4;
+        assert_eq!(
+            s.range(3..).iter().collect::<Vec<_>>(),
+            vec![Entity(0), Entity(5)]
+        );
+        assert_eq!(s.remove(Entity(0)), Some(3));
+        assert_eq!(s.keys().collect::<Vec<_>>(), vec![&2, &4]);
+
+        let mut other = Storage::new();
+        other.insert(Entity(2), "two");
+        other.insert(Entity(5), "five");
+        let joined: Vec<_> = (&s, &other, s.find(&4)).join().collect();
+        assert_eq!(joined, vec![(&4, &"five", Entity(5))]);
+
+        s.drain().join().for_each(drop);
+        assert_eq!(s.keys().count(), 0);
+    }
+
+    #[test]
+    fn spatial() {
+        use spatial::GridStorage;
+
+        let mut s = GridStorage::with_cell_size(1.0);
+        for i in 0..10 {
+            s.insert(Entity(i), (i as f32, -(i as f32)));
+        }
+        let ids = |set: set::EntitySet| set.iter().map(|e| e.0).collect::<Vec<_>>();
+        assert_eq!(ids(s.query_aabb((1.5, -4.0), (4.0, 0.0))), vec![2, 3, 4]);
+        assert_eq!(
+            ids(s.query_aabb((-100.0, -100.0), (100.0, 100.0))).len(),
+            10
+        );
+        assert_eq!(ids(s.query_radius((0.0, 0.0), 1.5)), vec![0, 1]);
+
+        *s.get_mut(Entity(9)).unwrap() = (0.2, 0.2);
+        assert_eq!(
+            s.nearest_k((0.0, 0.0), 3),
+            vec![Entity(0), Entity(9), Entity(1)]
+        );
+        assert_eq!(s.nearest_k((7.0, -7.0), 20).len(), 10);
+
+        assert_eq!(s.remove(Entity(0)), Some((0.0, 0.0)));
+        assert_eq!(s.insert(Entity(9), (7.0, -7.0)), Some((0.2, 0.2)));
+        assert_eq!(ids(s.query_radius((0.0, 0.0), 1.0)), vec![]);
+        assert_eq!(ids(s.query_radius((7.0, -7.0), 0.5)), vec![7, 9]);
+
+        let mut other = Storage::new();
+        other.insert(Entity(7), "seven");
+        let joined: Vec<_> = (&s, &other).join().collect();
+        assert_eq!(joined, vec![(&(7.0, -7.0), &"seven")]);
+
+        let mut edge = GridStorage::with_cell_size(1.0);
+        for i in 0..10 {
+            edge.insert(Entity(i), (f32::MAX, i as f32 * 10.0));
+        }
+        assert_eq!(
+            edge.nearest_k((f32::MAX, 0.0), 2),
+            vec![Entity(0), Entity(1)]
+        );
+        let huge = edge.query_aabb((-1e30, -1e30), (1e30, 1e30));
+        assert_eq!(huge.len(), 0);
+        assert_eq!(s.query_aabb((-1e30, -1e30), (1e30, 1e30)).len(), 9);
+        assert_eq!(s.query_radius((0.0, 0.0), f32::INFINITY).len(), 9);
+        assert_eq!(edge.query_radius((0.0, 0.0), f32::INFINITY).len(), 10);
+    }
+
+    #[test]
+    fn event_channel() {
+        use event::EventChannel;
+
+        let mut channel = EventChannel::with_capacity(4);
+        let mut a = channel.register_reader();
+        channel.extend(vec![1, 2]);
+        let mut b = channel.register_reader();
+        channel.write(3);
+        assert_eq!(
+            channel.read(&mut a).copied().collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(channel.read(&mut b).copied().collect::<Vec<_>>(), vec![3]);
+
+        channel.update();
+        channel.write(4);
+        channel.update();
+        assert_eq!(channel.len(), 1);
+        assert_eq!(channel.read(&mut a).copied().collect::<Vec<_>>(), vec![4]);
+
+        channel.extend(vec![5, 6, 7, 8]);
+        assert_eq!(channel.len(), 4);
+        assert_eq!(
+            channel.read(&mut b).copied().collect::<Vec<_>>(),
+            vec![5, 6, 7, 8]
+        );
+        channel.update();
+        channel.update();
+        assert!(channel.is_empty());
+        assert_eq!(channel.read(&mut a).count(), 0);
+
+        let mut other = EventChannel::new();
+        other.write(1);
+        assert_eq!(other.read(&mut a).count(), 0);
+        other.write(2);
+        assert_eq!(other.read(&mut a).copied().collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn hooks() {
+        use std::sync::{Arc, Mutex};
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut s = Storage::new();
+        let mut t = SparseStorage::new();
+        for hooks in [s.hooks_mut(), t.hooks_mut()] {
+            let (a, b, c) = (Arc::clone(&log), Arc::clone(&log), Arc::clone(&log));
+            hooks
+                .on_insert(move |e, &v: &u32| a.lock().unwrap().push(("insert", e.0, v)))
+                .on_replace(move |e, _, &v| b.lock().unwrap().push(("replace", e.0, v)))
+                .on_remove(move |e, &v| c.lock().unwrap().push(("remove", e.0, v)));
+        }
+
+        s.insert(Entity(0), 1);
+        s.insert(Entity(0), 2);
+        s.insert(Entity(3), 3);
+        s.remove(Entity(0));
+        s.clear();
+        t.insert(Entity(1), 4);
+        t.insert(Entity(2), 5);
+        t.insert(Entity(5), 6);
+        let mut drain = t.drain().join();
+        assert_eq!(drain.next(), Some(4));
+        drop(drain);
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("insert", 0, 1),
+                ("replace", 0, 2),
+                ("insert", 3, 3),
+                ("remove", 0, 2),
+                ("remove", 3, 3),
+                ("insert", 1, 4),
+                ("insert", 2, 5),
+                ("insert", 5, 6),
+                ("remove", 1, 4),
+                ("remove", 2, 5),
+                ("remove", 5, 6),
+            ]
+        );
+
+        log.lock().unwrap().clear();
+        s.insert(Entity(4), 7);
+        t.insert(Entity(6), 8);
+        let u = t.clone();
+        drop((s, t));
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                ("insert", 4, 7),
+                ("insert", 6, 8),
+                ("remove", 4, 7),
+                ("remove", 6, 8)
+            ]
+        );
+        drop(u);
+        assert_eq!(log.lock().unwrap().last(), Some(&("remove", 6, 8)));
+        assert_eq!(log.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn component() {
+        use component::{Component, StorageFor};
+
+        struct Pos;
+        struct Tag;
+        #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+        struct Index;
+        component!(Pos, Tag => SparseStorage, Index => indexed::IndexedStorage,);
+
+        fn storage<T: Component>() -> StorageFor<T> {
+            T::Storage::default()
+        }
+
+        let _: Storage<Pos> = storage::<Pos>();
+        let _: SparseStorage<Tag> = storage::<Tag>();
+        let _: indexed::IndexedStorage<Index> = storage::<Index>();
+    }
+
+    #[test]
+    fn component_storage() {
+        use component::ComponentStorage;
+
+        fn check<S: ComponentStorage<(f32, f32)>>() {
+            let mut s = S::new();
+            assert!(s.is_empty());
+            s.insert(Entity(4), (1.0, 2.0));
+            s.insert(Entity(1), (3.0, 4.0));
+            s.get_mut(Entity(4)).unwrap().0 = 5.0;
+            assert!(s.contains(Entity(1)));
+            assert!(!s.contains(Entity(2)));
+            assert_eq!(
+                s.iter().collect::<Vec<_>>(),
+                vec![(Entity(1), &(3.0, 4.0)), (Entity(4), &(5.0, 2.0))]
+            );
+            assert_eq!(s.remove(Entity(1)), Some((3.0, 4.0)));
+            assert_eq!(s.len(), 1);
+            assert_eq!(s.drain().join().collect::<Vec<_>>(), vec![(5.0, 2.0)]);
+            assert!(s.is_empty());
+            s.insert(Entity(0), (0.0, 0.0));
+            s.clear();
+            assert_eq!(s.len(), 0);
+        }
+
+        check::<Storage<_>>();
+        check::<SparseStorage<_>>();
+        check::<spatial::GridStorage<_>>();
+    }
+
+    #[test]
+    fn with_entities() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+
+        d.insert(Entity(0), 7);
+        d.insert(Entity(1), 12);
+        d.insert(Entity(4), 3);
+        e.insert(Entity(1), 17);
+        e.insert(Entity(6), 0);
+
+        let joined: Vec<_> = (&mut d, !&e).join().with_entities().collect();
+        assert_eq!(
+            joined,
+            vec![(Entity(0), (&mut 7, ())), (Entity(4), (&mut 3, ()))]
+        );
+
+        let joined: Vec<_> = (&e).join().with_entities().collect();
+        assert_eq!(joined, vec![(Entity(1), &17), (Entity(6), &0)]);
+    }
+
+    #[test]
+    fn size_hint() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+
+        d.insert(Entity(0), 7);
+        d.insert(Entity(1), 12);
+        d.insert(Entity(4), 3);
+        d.insert(Entity(4), 5);
+        e.insert(Entity(1), 17);
+        e.insert(Entity(6), 0);
+        assert_eq!((d.len(), e.len()), (3, 2));
+
+        let mut iter = (&d).join();
+        assert_eq!(iter.len(), 3);
+        iter.next();
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!((&mut d).join().count(), 3);
+        assert_eq!((&mut e).join().len(), 2);
+        let mut iter = (&e).join().with_entities();
+        iter.next();
+        assert_eq!(iter.len(), 1);
+
+        assert_eq!((&d, &e).join().size_hint(), (0, Some(2)));
+        assert_eq!((&d, &e).join().count(), 1);
+        assert_eq!((&d, !&e, Entities).join().size_hint(), (0, Some(3)));
+        assert_eq!((&d, !&e, Entities).join().count(), 2);
+
+        d.remove(Entity(0));
+        d.drain().join().for_each(drop);
+        assert!(d.is_empty());
+        e.clear();
+        assert!(e.is_empty());
+    }
+
+    #[test]
+    fn double_ended() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+
+        for i in [0, 2, 3, 5, 8, 9] {
+            d.insert(Entity(i), i as u32);
+        }
+        for i in [1, 2, 5, 9, 11] {
+            e.insert(Entity(i), i as u8);
+        }
+
+        let rev: Vec<_> = (&d).join().rev().collect();
+        assert_eq!(rev, vec![&9, &8, &5, &3, &2, &0]);
+        let rev: Vec<_> = (&mut e).join().rev().map(|c| *c).collect();
+        assert_eq!(rev, vec![11, 9, 5, 2, 1]);
+        let rev: Vec<_> = (&mut d, &e, Entities).join().rev().collect();
+        assert_eq!(
+            rev,
+            vec![
+                (&mut 9, &9, Entity(9)),
+                (&mut 5, &5, Entity(5)),
+                (&mut 2, &2, Entity(2))
+            ]
+        );
+        let rev: Vec<_> = (&e, !&d).join().with_entities().rev().collect();
+        assert_eq!(rev, vec![(Entity(11), (&11, ())), (Entity(1), (&1, ()))]);
+        let rev: Vec<_> = (&d, !&e).join().with_entities().rev().collect();
+        assert_eq!(
+            rev,
+            vec![
+                (Entity(8), (&8, ())),
+                (Entity(3), (&3, ())),
+                (Entity(0), (&0, ()))
+            ]
+        );
+
+        let mut iter = (&mut d, (&mut e).maybe()).join().with_entities();
+        assert_eq!(iter.next_back(), Some((Entity(9), (&mut 9, Some(&mut 9)))));
+        assert_eq!(iter.next(), Some((Entity(0), (&mut 0, None))));
+        assert_eq!(iter.next_back(), Some((Entity(8), (&mut 8, None))));
+        assert_eq!(iter.size_hint(), (0, Some(3)));
+        assert_eq!(iter.next(), Some((Entity(2), (&mut 2, Some(&mut 2)))));
+        assert_eq!(iter.next_back(), Some((Entity(5), (&mut 5, Some(&mut 5)))));
+        assert_eq!(iter.next_back(), Some((Entity(3), (&mut 3, None))));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn subsets() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+
+        for i in 0..8 {
+            d.insert(Entity(i), i as u32);
+        }
+        for i in [1, 3, 4, 9] {
+            e.insert(Entity(i), i as u8);
+        }
+
+        let range: Vec<_> = (&d, &e).join_range(2..5).with_entities().collect();
+        assert_eq!(range, vec![(Entity(3), (&3, &3)), (Entity(4), (&4, &4))]);
+        let range: Vec<_> = (&mut d, !&e).join_range(3..=6).rev().collect();
+        assert_eq!(range, vec![(&mut 6, ()), (&mut 5, ())]);
+        assert_eq!((&e).join_range(4..).count(), 2);
+        assert_eq!((&e).join_range(12..).next(), None);
+        assert_eq!((&d).join_range(..2).len(), 2);
+
+        let selected = [Entity(9), Entity(4), Entity(0), Entity(4), Entity(20)];
+        for (d, e) in (&mut d, (&mut e).maybe()).join_entities(&selected) {
+            *d += u32::from(e.map_or(100, |e| *e));
+        }
+        assert_eq!(d.get(Entity(0)), Some(&100));
+        assert_eq!(d.get(Entity(4)), Some(&8));
+        let subset: Vec<_> = (&e, !&d, Entities).join_entities(&selected).collect();
+        assert_eq!(subset, vec![(&9, (), Entity(9))]);
+        let subset: Vec<_> = (&d).join_entities(&selected).with_entities().collect();
+        assert_eq!(subset, vec![(Entity(0), &100), (Entity(4), &8)]);
+        assert_eq!((&d).join_entities(&[]).next(), None);
+    }
+
+    #[test]
+    fn ranges_stop_at_their_end() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+        let mut p = paged::PagedStorage::new();
+        for i in [0, 5, 9] {
+            d.insert(Entity(i), i as u32);
+            e.insert(Entity(i), i as u8);
+            p.insert(Entity(i), i);
+        }
+
+        assert_eq!((&d).join_range(..2).collect::<Vec<_>>(), vec![&0]);
+        assert_eq!((&e).join_range(..2).collect::<Vec<_>>(), vec![&0]);
+        assert_eq!((&d).join_range(1..6).len(), 1);
+        assert_eq!((&mut d).join_range(1..6).len(), 1);
+        assert_eq!((&e).join_range(1..6).len(), 1);
+        assert_eq!((&mut e).join_range(1..6).len(), 1);
+        assert_eq!((&p).join_range(1..6).len(), 1);
+        assert_eq!((&mut p).join_range(1..6).len(), 1);
+        assert_eq!((&d).join_range(1..6).count(), 1);
+        assert_eq!((&d, &e).join_range(1..6).count(), 1);
+        let set: set::EntitySet = vec![Entity(0), Entity(5), Entity(9)].into_iter().collect();
+        assert_eq!((&set).join_range(1..6).len(), 1);
+        assert_eq!(set.join_range(..=5).len(), 2);
+        assert_eq!((&mut d).join_range(1..=5).collect::<Vec<_>>(), vec![&mut 5]);
+
+        let far = 3 * paged::PAGE_SIZE + 1;
+        p.insert(Entity(far), far);
+        let selected = [Entity(far), Entity(5), Entity(6)];
+        let subset: Vec<_> = (&mut p).join_entities(&selected).collect();
+        assert_eq!(subset, vec![&mut 5, &mut { far }]);
+        assert_eq!((&p, &d).join_entities(&selected).count(), 1);
+
+        let mut drain = p.drain().join_range(1..6);
+        assert_eq!(drain.len(), 1);
+        assert_eq!(drain.next(), Some(5));
+        assert_eq!(drain.next(), None);
+        drop(drain);
+        assert!(p.is_empty());
+        assert_eq!(d.drain().join_range(6..).collect::<Vec<_>>(), vec![9]);
+
+        for i in 0..8 {
+            d.insert(Entity(i), i as u32);
+            e.insert(Entity(i), i as u8);
+        }
+        let drained: Vec<_> = d.drain_filter(|_, _| true).join_range(5..7).collect();
+        assert_eq!(drained, vec![5, 6]);
+        let drained: Vec<_> = e.drain_filter(|_, _| true).join_range(5..7).collect();
+        assert_eq!(drained, vec![5, 6]);
+        let drained: Vec<_> = (d.drain_filter(|_, _| true), &e).join_range(2..4).collect();
+        assert_eq!(drained, vec![(2, &2), (3, &3)]);
+        assert_eq!((&d).join().collect::<Vec<_>>(), vec![&0, &1, &4, &7]);
+        assert_eq!(
+            (&e).join().collect::<Vec<_>>(),
+            vec![&0, &1, &2, &3, &4, &7, &9]
+        );
+    }
+
+    #[test]
+    fn fetch() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+
+        d.insert(Entity(0), 7);
+        d.insert(Entity(2), 12);
+        e.insert(Entity(2), 17);
+        e.insert(Entity(5), 3);
+
+        assert_eq!((&d, &e).fetch(Entity(2)), Some((&12, &17)));
+        assert_eq!((&d, &e).fetch(Entity(0)), None);
+        assert_eq!(
+            (&d, !&e, Entities).fetch(Entity(0)),
+            Some((&7, (), Entity(0)))
+        );
+        assert_eq!((!&d, &mut e).fetch(Entity(5)), Some(((), &mut 3)));
+        assert_eq!((&e, (&d).maybe()).fetch(Entity(5)), Some((&3, None)));
+        assert_eq!((&e, (&d).maybe()).fetch(Entity(2)), Some((&17, Some(&12))));
+        assert_eq!((&e).maybe().fetch(Entity(9)), Some(None));
+
+        if let Some((d, e)) = (&mut d, &&mut e).fetch(Entity(2)) {
+            *d += u32::from(*e);
+        }
+        assert_eq!(d.get(Entity(2)), Some(&29));
+
+        let set: set::EntitySet = vec![Entity(0), Entity(5)].into_iter().collect();
+        assert_eq!((&set, &e).fetch(Entity(5)), Some((Entity(5), &3)));
+        assert_eq!((&set, &d).fetch(Entity(2)), None);
+    }
+
+    #[test]
+    fn get_many_mut() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+
+        for i in [0, 2, 3, 6] {
+            d.insert(Entity(i), i as u32);
+            e.insert(Entity(i), i as u8);
+        }
+
+        let [a, b, c] = d.get_many_mut([Entity(6), Entity(0), Entity(3)]).unwrap();
+        assert_eq!((*a, *b, *c), (6, 0, 3));
+        *a += 1;
+        assert_eq!(d.get(Entity(6)), Some(&7));
+        let [a, b] = e.get_many_mut([Entity(3), Entity(2)]).unwrap();
+        mem::swap(a, b);
+        assert_eq!(e.get(Entity(2)), Some(&3));
+        assert!(d.get_many_mut::<0>([]).is_ok());
+
+        for (missing, err) in [
+            (Entity(1), GetManyError::Missing(Entity(1))),
+            (Entity(9), GetManyError::Missing(Entity(9))),
+            (Entity(2), GetManyError::Duplicate(Entity(2))),
+        ] {
+            assert_eq!(d.get_many_mut([Entity(2), missing]).err(), Some(err));
+            assert_eq!(e.get_many_mut([Entity(2), missing]).err(), Some(err));
+        }
+    }
+
+    #[test]
+    fn combinations() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u8>::new();
+
+        for i in 0..5 {
+            d.insert(Entity(i), i as u32);
+        }
+        e.insert(Entity(3), 0);
+
+        assert_eq!((&d).iter_combinations::<3>().count(), 10);
+        assert_eq!((&d).iter_combinations::<5>().count(), 1);
+        assert_eq!((&d).iter_combinations::<6>().count(), 0);
+        assert_eq!((&d).iter_combinations::<0>().count(), 1);
+        assert_eq!((&d, &e).iter_combinations::<2>().count(), 0);
+
+        for [(a, x), (b, y), (c, z)] in (&d).iter_combinations::<3>() {
+            assert!(a < b && b < c);
+            assert_eq!((a.0, b.0, c.0), (*x as usize, *y as usize, *z as usize));
+        }
+        let mut triples = (&d, Entities).iter_combinations::<3>();
+        assert_eq!(triples.nth(9).map(|[_, _, (c, _)]| c), Some(Entity(4)));
+        assert_eq!(triples.next(), None);
+        for i in 0..5 {
+            *d.get_mut(Entity(i)).unwrap() += 6;
+        }
+
+        d.remove(Entity(0));
+        d.remove(Entity(2));
+        d.insert(Entity(7), 0);
+        let mut pairs = d.pairs_mut();
+        let mut seen = Vec::new();
+        while let Some([(a, x), (b, y)]) = pairs.fetch_next() {
+            seen.push((a.0, b.0));
+            *x += 1;
+            *y += 1;
+        }
+        assert_eq!(pairs.fetch_next(), None);
+        assert_eq!(seen, vec![(1, 3), (1, 4), (1, 7), (3, 4), (3, 7), (4, 7)]);
+        let counts: Vec<_> = (&d).join().copied().collect();
+        assert_eq!(counts, vec![10, 12, 13, 3]);
+        assert_eq!(Storage::<u32>::new().pairs_mut().fetch_next(), None);
+
+        let mut sparse = SparseStorage::new();
+        for (e, &c) in (&d).join().with_entities() {
+            sparse.insert(e, c);
+        }
+        let mut pairs = sparse.pairs_mut();
+        let mut seen = Vec::new();
+        while let Some([(a, x), (b, y)]) = pairs.fetch_next() {
+            seen.push((a.0, b.0));
+            *x += 1;
+            *y += 1;
+        }
+        assert_eq!(seen, vec![(1, 3), (1, 4), (1, 7), (3, 4), (3, 7), (4, 7)]);
+        assert_eq!(
+            (&sparse).join().copied().collect::<Vec<_>>(),
+            vec![13, 15, 16, 6]
+        );
+        assert_eq!(SparseStorage::<u32>::new().pairs_mut().fetch_next(), None);
+    }
+
+    #[test]
+    fn retain() {
+        use std::sync::{Arc, Mutex};
+
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u32>::new();
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let r = Arc::clone(&removed);
+        e.hooks_mut()
+            .on_remove(move |e, _| r.lock().unwrap().push(e));
+
+        for i in 0..6 {
+            d.insert(Entity(i), i as u32);
+            e.insert(Entity(i), i as u32);
+        }
+
+        d.retain(|_, c| {
+            *c += 1;
+            *c % 2 == 0
+        });
+        e.retain(|e, _| e.0 != 4);
+        assert_eq!(d.len(), 3);
+        assert_eq!((&d).join().copied().collect::<Vec<_>>(), vec![2, 4, 6]);
+        assert_eq!(*removed.lock().unwrap(), vec![Entity(4)]);
+
+        let drained: Vec<_> = (e.drain_filter(|_, &mut c| c > 1), &d, Entities)
+            .join()
+            .collect();
+        assert_eq!(drained, vec![(3, &4, Entity(3)), (5, &6, Entity(5))]);
+        assert_eq!(e.len(), 3);
+        assert_eq!(removed.lock().unwrap().len(), 3);
+
+        assert_eq!(d.drain_filter(|e, _| e.0 > 2).join().next(), Some(4));
+        assert_eq!(d.len(), 2);
+        assert_eq!((&d).join().copied().collect::<Vec<_>>(), vec![2, 6]);
+    }
+
+    #[test]
+    fn drain_filter_only_removes_joined() {
+        let mut a: Storage<u32> = Storage::new();
+        let mut b = SparseStorage::new();
+        let mut c = SparseStorage::new();
+        for i in 0..6 {
+            a.insert(Entity(i), i as u32);
+            b.insert(Entity(i), i as u32);
+        }
+        c.insert(Entity(2), ());
+        c.insert(Entity(3), ());
+
+        let drained: Vec<_> = (a.drain_filter(|_, _| true), !&c).join().collect();
+        assert_eq!(drained, vec![(0, ()), (1, ()), (4, ()), (5, ())]);
+        assert_eq!((&a).join().copied().collect::<Vec<_>>(), vec![2, 3]);
+
+        let drained: Vec<_> = (
+            &c,
+            (
+                b.drain_filter(|_, _| true),
+                a.drain_filter(|_, &mut v| v > 2),
+            ),
+        )
+            .join()
+            .collect();
+        assert_eq!(drained, vec![(&(), (3, 3))]);
+        assert_eq!(b.len(), 5);
+        assert_eq!(a.len(), 1);
+
+        let mut all = Storage::new();
+        for i in 0..6 {
+            all.insert(Entity(i), ());
+        }
+        let mut calls = 0;
+        let filter = b.drain_filter(|_, _| {
+            calls += 1;
+            true
+        });
+        let drained: Vec<_> = (&all, filter.maybe(), !&c)
+            .join()
+            .map(|(_, b, _)| b)
+            .collect();
+        assert_eq!(drained, vec![Some(0), Some(1), Some(4), Some(5)]);
+        // The filter is called once per component, including the kept one of `Entity(2)`.
+        assert_eq!(calls, 5);
+        assert_eq!(b.len(), 1);
+        for i in 0..6 {
+            a.insert(Entity(i), i as u32);
+            b.insert(Entity(i), i as u32);
+        }
+        assert_eq!(a.drain_filter(|_, _| true).fetch(Entity(3)), Some(3));
+        assert_eq!(b.drain_filter(|_, _| true).fetch(Entity(3)), Some(3));
+        assert_eq!(a.drain_filter(|_, &mut v| v > 4).fetch(Entity(2)), None);
+        assert_eq!(b.drain_filter(|_, _| true).fetch(Entity(9)), None);
+        let rest = vec![&0, &1, &2, &4, &5];
+        assert_eq!((&a).join().collect::<Vec<_>>(), rest);
+        assert_eq!((&b).join().collect::<Vec<_>>(), rest);
+
+        // Components are only removed if all other members have the entity.
+        let fetched = (a.drain_filter(|_, _| true), &c).fetch(Entity(4));
+        assert_eq!(fetched, None);
+        let fetched = (&c, b.drain_filter(|_, _| true)).fetch(Entity(2));
+        assert_eq!(fetched, Some((&(), 2)));
+        assert_eq!((&a).join().collect::<Vec<_>>(), rest);
+        assert_eq!((&b).join().collect::<Vec<_>>(), vec![&0, &1, &4, &5]);
+    }
+
+    #[test]
+    fn drain_on_drop() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u32>::new();
+        for i in 0..6 {
+            d.insert(Entity(i), i as u32);
+            e.insert(Entity(i * 2), i as u32);
+        }
+
+        let mut drain = d.drain().with_entities();
+        assert_eq!(drain.next(), Some((Entity(0), 0)));
+        assert_eq!(drain.len(), 5);
+        drop(drain);
+        assert!(d.is_empty());
+        let mut drain = e.drain();
+        assert_eq!(drain.nth(3), None);
+        assert_eq!(drain.join().with_entities().next(), Some((Entity(4), 2)));
+        assert!(e.is_empty());
+
+        for i in 0..6 {
+            d.insert(Entity(i), i as u32);
+            e.insert(Entity(i * 2), i as u32);
+        }
+        let mut drain = d.drain();
+        assert_eq!(drain.nth(2), Some(2));
+        let joined: Vec<_> = (&e, drain, Entities).join().collect();
+        assert_eq!(joined, vec![(&2, 4, Entity(4))]);
+        assert!(d.is_empty());
+        assert_eq!(e.len(), 6);
+
+        let joined: Vec<_> = (e.drain(), &d).join().collect();
+        assert!(joined.is_empty());
+        assert!(e.is_empty());
+    }
+
+    #[test]
+    fn leaked_drains_keep_indices() {
+        let mut indexed = indexed::IndexedStorage::<u32>::new();
+        let mut grid = spatial::GridStorage::new();
+        let mut shared = shared::SharedStorage::new();
+        for i in 0..4 {
+            indexed.insert(Entity(i), i as u32 % 2);
+            grid.insert(Entity(i), (i as f32, 0.0));
+            shared.insert(Entity(i), i as u32 % 2);
+        }
+
+        let mut drain = indexed.drain();
+        assert_eq!(drain.nth(1), Some(1));
+        mem::forget(drain);
+        let found: Vec<_> = indexed.find(&1).iter().collect();
+        assert_eq!(found, vec![Entity(3)]);
+        assert_eq!(indexed.remove(Entity(2)), Some(0));
+        assert_eq!(indexed.remove(Entity(3)), Some(1));
+
+        let mut drain = grid.drain();
+        assert_eq!(drain.next(), Some((0.0, 0.0)));
+        mem::forget(drain);
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.remove(Entity(1)), Some((1.0, 0.0)));
+        assert_eq!(grid.query_aabb((0.0, 0.0), (1.0, 1.0)).len(), 0);
+
+        let mut drain = shared.drain();
+        assert_eq!(drain.nth(2), Some(0));
+        mem::forget(drain);
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared.remove(Entity(3)), Some(1));
+        drop(shared.drain());
+        assert!(shared.is_empty());
+    }
+
+    #[test]
+    fn memory_usage() {
+        let mut d: Storage<u32> = Storage::new();
+        let mut e = SparseStorage::<u32>::new();
+
+        d.reserve(10);
+        assert!(d.memory_usage().capacity >= 10);
+        d.insert(Entity(2), 0);
+        d.insert(Entity(100), 1);
+        e.insert(Entity(100), 1);
+        assert!(d.memory_usage().bytes >= 101 * mem::size_of::<Option<u32>>());
+
+        d.remove(Entity(100));
+        d.shrink_to_fit();
+        let usage = d.memory_usage();
+        assert_eq!(usage.len, 1);
+        assert!((3..100).contains(&usage.capacity));
+        assert_eq!(usage.bytes, usage.capacity * mem::size_of::<Option<u32>>());
+        assert_eq!((&d).join().len(), 1);
+        d.clear();
+        d.shrink_to_fit();
+        assert_eq!(d.memory_usage().capacity, 0);
+
+        e.reserve(10);
+        e.shrink_to_fit();
+        assert_eq!(e.memory_usage().len, 1);
+    }
+
+    #[test]
+    fn paged() {
+        use paged::{PagedStorage, PAGE_SIZE};
+
+        let mut d: Storage<u32> = Storage::new();
+        let mut p = PagedStorage::new();
+
+        let ids = [
+            1,
+            3,
+            PAGE_SIZE - 1,
+            PAGE_SIZE,
+            5 * PAGE_SIZE + 2,
+            9 * PAGE_SIZE,
+        ];
+        for &i in ids.iter() {
+            d.insert(Entity(i), i as u32);
+            p.insert(Entity(i), i as u32);
+        }
+        d.remove(Entity(3));
+        assert_eq!(p.len(), 6);
+        assert_eq!(p.memory_usage().capacity, 4 * PAGE_SIZE);
+
+        let joined: Vec<_> = (&p).join().with_entities().map(|(e, _)| e.0).collect();
+        assert_eq!(joined, ids);
+        let joined: Vec<_> = (&d, &p).join().map(|(a, b)| (*a, *b)).collect();
+        assert_eq!(joined.len(), 5);
+        assert!(joined.iter().all(|(a, b)| a == b));
+        assert_eq!((&p, !&d).join().count(), 1);
+
+        let mut iter = (&p).join();
+        assert_eq!(iter.len(), 6);
+        iter.next();
+        assert_eq!(iter.len(), 5);
+
+        for (p, e) in (&mut p, Entities).join_range(4..) {
+            *p += e.0 as u32;
+        }
+        assert_eq!(p.get(Entity(PAGE_SIZE)), Some(&(2 * PAGE_SIZE as u32)));
+        assert_eq!(p.get(Entity(3)), Some(&3));
+        let mut iter = (&mut d, &mut p).join();
+        assert_eq!(iter.nth(1).map(|(a, b)| (*a, *b)), Some((255, 510)));
+        assert_eq!(iter.count(), 3);
+
+        assert_eq!(
+            p.remove(Entity(5 * PAGE_SIZE + 2)),
+            Some(10 * PAGE_SIZE as u32 + 4)
+        );
+        assert_eq!(p.memory_usage().capacity, 3 * PAGE_SIZE);
+        let drained: Vec<_> = (p.drain(), &d).join().collect();
+        assert_eq!(drained.len(), 4);
+        assert!(p.is_empty());
+        p.shrink_to_fit();
+        assert_eq!(p.memory_usage().bytes, 0);
+    }
+
+    crate::soa_storage! {
+        mod particle {
+            pub struct Particle {
+                pub mass: u32,
+                pub velocity: (i32, i32),
+                pub(super) name: String,
+            }
+        }
+    }
+
+    #[test]
+    fn soa_storage() {
+        use particle::Particle;
+
+        let mut d: Storage<u32> = Storage::new();
+        let mut p = particle::Storage::new();
+        for i in 0..6 {
+            d.insert(Entity(i), i as u32);
+            if i % 2 == 1 {
+                let name = format!("p{}", i);
+                let velocity = (i as i32, 0);
+                p.insert(
+                    Entity(i),
+                    Particle {
+                        mass: 1,
+                        velocity,
+                        name,
+                    },
+                );
+            }
+        }
+
+        assert_eq!(p.len(), 3);
+        assert_eq!((&p).join().len(), 3);
+        assert_eq!((&p).join_range(2..6).len(), 2);
+        assert_eq!((&mut p).join_range(2..6).len(), 2);
+        let columns = p.columns();
+        let joined: Vec<_> = (columns.name, &d).join().collect();
+        assert_eq!(
+            joined,
+            vec![
+                (&"p1".to_string(), &1),
+                (&"p3".to_string(), &3),
+                (&"p5".to_string(), &5)
+            ]
+        );
+        assert_eq!(columns.velocity.join().len(), 3);
+
+        for (mass, d) in (p.columns_mut().mass, &d).join_range(2..) {
+            *mass += d;
+        }
+        let masses: Vec<_> = (&p, Entities).join().map(|(p, e)| (e.0, *p.mass)).collect();
+        assert_eq!(masses, vec![(1, 1), (3, 4), (5, 6)]);
+
+        let view = (&mut p, &d).fetch(Entity(3)).unwrap().0;
+        view.velocity.1 = 7;
+        assert_eq!(p.get(Entity(3)).map(|p| *p.velocity), Some((3, 7)));
+        assert!((&mut p).fetch(Entity(2)).is_none());
+
+        let previous = p.insert(
+            Entity(5),
+            Particle {
+                mass: 2,
+                velocity: (0, 0),
+                name: String::new(),
+            },
+        );
+        assert_eq!(previous.map(|p| p.name), Some("p5".to_string()));
+        assert_eq!(p.remove(Entity(3)).map(|p| p.velocity), Some((3, 7)));
+        assert_eq!(p.remove(Entity(3)).map(|p| p.mass), None);
+        p.clear();
+        assert!(p.is_empty());
+        assert_eq!((&p).join().count(), 0);
+    }
+
+    #[test]
+    fn archetypes() {
+        use crate::archetype::Archetypes;
+        use std::rc::Rc;
+
+        let mut world = Archetypes::new();
+        let shared = Rc::new(());
+        for i in 0..6 {
+            world.insert(Entity(i), i as u32);
+            if i % 2 == 0 {
+                world.insert(Entity(i), -(i as i64));
+            }
+            if i % 3 == 0 {
+                world.insert(Entity(i), shared.clone());
+            }
+        }
+        assert_eq!(world.len(), 6);
+        assert_eq!(world.archetype_count(), 4);
+        assert_eq!(Rc::strong_count(&shared), 3);
+
+        for (a, b) in world.query_mut::<(&mut u32, &i64)>() {
+            *a = (*a as i64 + *b + 10) as u32;
+        }
+        let mut both: Vec<_> = world
+            .query::<(Entity, &u32, &i64)>()
+            .map(|(e, &a, &b)| (e.0, a, b))
+            .collect();
+        both.sort_unstable();
+        assert_eq!(both, vec![(0, 10, 0), (2, 10, -2), (4, 10, -4)]);
+        assert_eq!(world.query::<&u32>().count(), 6);
+
+        assert_eq!(world.insert(Entity(3), 7u32), Some(3));
+        assert_eq!(world.remove::<i64>(Entity(0)), Some(0));
+        assert_eq!(world.remove::<i64>(Entity(0)), None);
+        assert_eq!(world.get::<u32>(Entity(0)), Some(&10));
+        assert!(world.contains::<Rc<()>>(Entity(0)));
+        assert_eq!(world.get::<i64>(Entity(4)), Some(&-4));
+        assert_eq!(world.get::<u32>(Entity(4)), Some(&10));
+
+        assert!(world.despawn(Entity(3)));
+        assert!(!world.despawn(Entity(3)));
+        assert_eq!(Rc::strong_count(&shared), 2);
+        assert_eq!(world.remove::<u32>(Entity(1)), Some(1));
+        assert_eq!(world.len(), 4);
+        assert_eq!(world.get::<u32>(Entity(5)), Some(&5));
+
+        let mut rest: Vec<_> = world.query::<Entity>().map(|e| e.0).collect();
+        rest.sort_unstable();
+        assert_eq!(rest, vec![0, 2, 4, 5]);
+        assert!(format!("{:?}", world).contains("Entity(5)"));
+    }
+
+    #[test]
+    fn join_chunks() {
+        use crate::chunk::ChunkJoin;
+        use particle::{Particle, Storage};
+
+        let particle = |mass| Particle {
+            mass,
+            velocity: (0, 0),
+            name: String::new(),
+        };
+        let mut a = Storage::new();
+        let mut b = Storage::new();
+        for i in [0, 1, 2, 3, 6, 7, 8, 11] {
+            a.insert(Entity(i), particle(i as u32));
+        }
+        for i in [1, 2, 4, 5, 6, 7, 8, 9, 10, 11, 12] {
+            b.insert(Entity(i), particle(1));
+        }
+
+        let lengths: Vec<_> = a.columns().mass.join_chunks().map(<[_]>::len).collect();
+        assert_eq!(lengths, vec![4, 3, 1]);
+
+        let chunks: Vec<_> = (a.columns().mass, b.columns_mut().mass)
+            .join_chunks()
+            .map(|(a, b)| {
+                for (a, b) in a.iter().zip(b.iter_mut()) {
+                    *b += a;
+                }
+                a.to_vec()
+            })
+            .collect();
+        assert_eq!(chunks, vec![vec![1, 2], vec![6, 7, 8], vec![11]]);
+        let masses: Vec<_> = (&b).join().map(|p| *p.mass).collect();
+        assert_eq!(masses, vec![2, 3, 1, 1, 7, 8, 9, 1, 1, 12, 1]);
+
+        let empty = Storage::new();
+        assert_eq!(
+            (a.columns().mass, empty.columns().mass)
+                .join_chunks()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn shared_storage() {
+        use crate::shared::SharedStorage;
+        use std::sync::Arc;
+
+        let mut s = SharedStorage::new();
+        for i in 0..6 {
+            s.insert(Entity(i), vec![i % 2; 4]);
+        }
+        assert_eq!(s.len(), 6);
+        assert_eq!(s.unique_len(), 2);
+        assert!(Arc::ptr_eq(
+            s.get_shared(Entity(0)).unwrap(),
+            s.get_shared(Entity(4)).unwrap()
+        ));
+
+        s.get_mut(Entity(0)).unwrap()[0] = 7;
+        assert_eq!(s.unique_len(), 3);
+        assert_eq!(s.get(Entity(0)), Some(&vec![7, 0, 0, 0]));
+        assert_eq!(s.get(Entity(2)), Some(&vec![0; 4]));
+
+        // becomes shared again once equal
+        s.get_mut(Entity(0)).unwrap()[0] = 0;
+        assert_eq!(s.unique_len(), 2);
+        assert!(Arc::ptr_eq(
+            s.get_shared(Entity(0)).unwrap(),
+            s.get_shared(Entity(2)).unwrap()
+        ));
+
+        // reading through the guard does not clone
+        assert_eq!(s.get_mut(Entity(1)).unwrap().len(), 4);
+        assert!(Arc::ptr_eq(
+            s.get_shared(Entity(1)).unwrap(),
+            s.get_shared(Entity(3)).unwrap()
+        ));
+
+        let copy = s.clone();
+        assert_eq!(s.insert(Entity(1), vec![0; 4]), Some(vec![1; 4]));
+        assert_eq!(s.remove(Entity(3)), Some(vec![1; 4]));
+        assert_eq!(s.unique_len(), 2);
+        s.get_mut(Entity(5)).unwrap().push(1);
+        assert_eq!(s.remove(Entity(5)), Some(vec![1; 5]));
+        assert_eq!(s.unique_len(), 1);
+        assert_eq!(copy.get(Entity(5)), Some(&vec![1; 4]));
+
+        let mut health = Storage::new();
+        health.insert(Entity(2), 3);
+        health.insert(Entity(3), 4);
+        let joined: Vec<_> = (&health, &s).join().map(|(h, v)| (*h, v.len())).collect();
+        assert_eq!(joined, vec![(3, 4)]);
+        assert_eq!((&s).join().rev().count(), 4);
+        assert_eq!((&s).fetch(Entity(4)), Some(&vec![0; 4]));
+
+        let drained: Vec<_> = s.drain().with_entities().map(|(e, _)| e.0).collect();
+        assert_eq!(drained, vec![0, 1, 2, 4]);
+        assert!(s.is_empty());
+        assert_eq!(s.unique_len(), 0);
+    }
+
+    #[test]
+    fn default_storage() {
+        use crate::default::DefaultStorage;
+
+        let mut tint = DefaultStorage::new(1);
+        tint.insert(Entity(2), 5);
+        assert_eq!(tint.get(Entity(100)), &1);
+        assert_eq!(tint.get_override(Entity(100)), None);
+        *tint.get_mut(Entity(4)) += 2;
+        assert_eq!(tint.overrides(), 2);
+
+        let mut position = Storage::new();
+        for i in [1, 2, 4, 6] {
+            position.insert(Entity(i), i);
+        }
+        let joined: Vec<_> = (&position, &tint).join().map(|(p, t)| (*p, *t)).collect();
+        assert_eq!(joined, vec![(1, 1), (2, 5), (4, 3), (6, 1)]);
+        let back: Vec<_> = (&position, &tint)
+            .join()
+            .rev()
+            .map(|(p, t)| (*p, *t))
+            .collect();
+        assert_eq!(back, vec![(6, 1), (4, 3), (2, 5), (1, 1)]);
+
+        let first: Vec<_> = (&tint, Entities)
+            .join()
+            .take(3)
+            .map(|(t, e)| (e.0, *t))
+            .collect();
+        assert_eq!(first, vec![(0, 1), (1, 1), (2, 5)]);
+        assert_eq!((&tint).join_range(3..6).count(), 3);
+        assert_eq!((&tint).fetch(Entity(9)), Some(&1));
+
+        assert_eq!(tint.set_default(0), 1);
+        assert_eq!(tint.remove(Entity(2)), Some(5));
+        assert_eq!(tint.get(Entity(2)), &0);
+        assert_eq!(tint.default_value(), &0);
+
+        use crate::component::ComponentStorage;
+        assert_eq!(ComponentStorage::get(&tint, Entity(7)), None);
+        assert_eq!(ComponentStorage::get(&tint, Entity(4)), Some(&3));
+        assert!(!ComponentStorage::contains(&tint, Entity(7)));
+        assert!(ComponentStorage::contains(&tint, Entity(4)));
+        assert_eq!(ComponentStorage::len(&tint), 1);
+        let overrides: Vec<_> = ComponentStorage::iter(&tint).collect();
+        assert_eq!(overrides, vec![(Entity(4), &3)]);
+        assert_eq!(tint.drain().join().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(tint.overrides(), 0);
+    }
+
+    #[test]
+    fn wide_and_nested_tuples() {
+        let storages: Vec<Storage<usize>> = (0..16)
+            .map(|k| {
+                let mut s = Storage::new();
+                for i in (0..40).filter(|i| i % 2 == 0 || k % 2 == 0) {
+                    s.insert(Entity(i), i * 100 + k);
+                }
+                s.insert(Entity(40 + k), 0);
+                s
+            })
+            .collect();
+        let [a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p] = &storages[..] else {
+            unreachable!()
+        };
+
+        let wide: Vec<_> = (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p)
+            .join()
+            .map(|(a, b, .., o, p)| (*a, *b, *o, *p))
+            .collect();
+        assert_eq!(wide.len(), 20);
+        assert_eq!(wide[1], (200, 201, 214, 215));
+
+        let nested: Vec<_> = ((a, b, c, d), ((e, f), (g, h)), (i, j, k, l, m, n, o, p))
+            .join()
+            .map(|((a, b, ..), _, (.., o, p))| (*a, *b, *o, *p))
+            .collect();
+        assert_eq!(nested, wide);
+
+        let mut sparse = SparseStorage::new();
+        sparse.insert(Entity(4), ());
+        sparse.insert(Entity(38), ());
+        let joined = || ((a, b), (&sparse, (c, Entities)));
+        assert_eq!(joined().join().size_hint(), (0, Some(2)));
+        let entities: Vec<_> = joined().join().map(|(_, (_, (_, e)))| e.0).collect();
+        assert_eq!(entities, vec![4, 38]);
+        let back: Vec<_> = joined().join().rev().map(|(_, (_, (_, e)))| e.0).collect();
+        assert_eq!(back, vec![38, 4]);
+        assert_eq!(joined().join_range(5..).count(), 1);
+        assert!(joined().fetch(Entity(38)).is_some());
+        assert!(joined().fetch(Entity(39)).is_none());
+    }
 }
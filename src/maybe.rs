@@ -1,4 +1,4 @@
-use crate::{Join, Joinable, Joined};
+use crate::{DoubleEndedJoin, Join, Joinable, Joined};
 
 /// The iterator returned by calling `T::maybe()` on a `T` which implements `Joinable`.
 pub struct Maybe<T>(T);
@@ -21,17 +21,46 @@ impl<T: Iterator> Iterator for Maybe<T> {
     }
 }
 
-impl<T> Join for Maybe<T> {
+impl<T: DoubleEndedIterator> DoubleEndedIterator for Maybe<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        Some(self.0.next_back())
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        Some(self.0.nth_back(n))
+    }
+}
+
+impl<T: Join + Iterator> Join for Maybe<T> {
     fn may_skip(&mut self, _curr: usize) -> usize {
         0
     }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.0.seek(n)
+    }
+
+    fn removes_nth(&mut self, n: usize) -> Option<bool> {
+        // `nth` always yields an item, which may still remove one of `self.0`.
+        self.0.removes_nth(n).map(|_| true)
+    }
+}
+
+impl<T: DoubleEndedJoin> DoubleEndedJoin for Maybe<T> {
+    fn may_skip_back(&mut self) -> usize {
+        0
+    }
 }
 
-impl<T: Iterator> Joinable for Maybe<T> {
+impl<T: Join + Iterator> Joinable for Maybe<T> {
     type Joined = Maybe<T>;
     type Item = Option<T::Item>;
 
     fn join(self) -> Joined<Self::Joined> {
-        Joined::new(self, std::usize::MAX)
+        Joined::new(self, usize::MAX)
     }
 }
@@ -1,6 +1,8 @@
 use std::ops::Not;
 
-use crate::{Iter, Join, Joinable, Joined, SparseIter, SparseStorage, Storage};
+use crate::{
+    DoubleEndedJoin, Entity, Iter, Join, Joinable, Joined, SparseIter, SparseStorage, Storage,
+};
 
 pub struct NegatedStorage<'a, T>(&'a Storage<T>);
 
@@ -34,6 +36,29 @@ impl<'a, T> Join for NegatedIter<'a, T> {
     fn may_skip(&mut self, _curr: usize) -> usize {
         self.0.slice.iter().take_while(|opt| opt.is_some()).count()
     }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.0.seek(n)
+    }
+}
+
+impl<'a, T> DoubleEndedJoin for NegatedIter<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        if self.0.positions > self.0.slice.len() {
+            0
+        } else {
+            self.0
+                .slice
+                .iter()
+                .rev()
+                .take_while(|opt| opt.is_some())
+                .count()
+        }
+    }
 }
 
 impl<'a, T> Clone for NegatedIter<'a, T> {
@@ -46,7 +71,7 @@ impl<'a, T> Iterator for NegatedIter<'a, T> {
     type Item = ();
 
     fn next(&mut self) -> Option<()> {
-        if let Some(_) = self.0.next() {
+        if self.0.next().is_some() {
             None
         } else {
             Some(())
@@ -54,7 +79,21 @@ impl<'a, T> Iterator for NegatedIter<'a, T> {
     }
 
     fn nth(&mut self, n: usize) -> Option<()> {
-        if let Some(_) = self.0.nth(n) {
+        if self.0.nth(n).is_some() {
+            None
+        } else {
+            Some(())
+        }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for NegatedIter<'a, T> {
+    fn next_back(&mut self) -> Option<()> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<()> {
+        if self.0.nth_back(n).is_some() {
             None
         } else {
             Some(())
@@ -68,7 +107,15 @@ impl<'a, T> Joinable for NegatedStorage<'a, T> {
 
     fn join(self) -> Joined<Self::Joined> {
         let storage = self.0.join();
-        Joined::new(NegatedIter(storage.iter), std::usize::MAX)
+        Joined::new(NegatedIter(storage.iter), usize::MAX)
+    }
+
+    fn fetch(self, entity: Entity) -> Option<()> {
+        if self.0.get(entity).is_some() {
+            None
+        } else {
+            Some(())
+        }
     }
 }
 
@@ -106,6 +153,20 @@ impl<'a, T> Join for NegatedSparseIter<'a, T> {
     fn may_skip(&mut self, _curr: usize) -> usize {
         0
     }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.0.seek(n)
+    }
+}
+
+impl<'a, T> DoubleEndedJoin for NegatedSparseIter<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        0
+    }
 }
 
 impl<'a, T> Clone for NegatedSparseIter<'a, T> {
@@ -118,7 +179,7 @@ impl<'a, T> Iterator for NegatedSparseIter<'a, T> {
     type Item = ();
 
     fn next(&mut self) -> Option<()> {
-        if let Some(_) = self.0.next() {
+        if self.0.next().is_some() {
             None
         } else {
             Some(())
@@ -126,7 +187,21 @@ impl<'a, T> Iterator for NegatedSparseIter<'a, T> {
     }
 
     fn nth(&mut self, n: usize) -> Option<()> {
-        if let Some(_) = self.0.nth(n) {
+        if self.0.nth(n).is_some() {
+            None
+        } else {
+            Some(())
+        }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for NegatedSparseIter<'a, T> {
+    fn next_back(&mut self) -> Option<()> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<()> {
+        if self.0.nth_back(n).is_some() {
             None
         } else {
             Some(())
@@ -140,6 +215,14 @@ impl<'a, T> Joinable for NegatedSparseStorage<'a, T> {
 
     fn join(self) -> Joined<Self::Joined> {
         let storage = self.0.join();
-        Joined::new(NegatedSparseIter(storage.iter), std::usize::MAX)
+        Joined::new(NegatedSparseIter(storage.iter), usize::MAX)
+    }
+
+    fn fetch(self, entity: Entity) -> Option<()> {
+        if self.0.get(entity).is_some() {
+            None
+        } else {
+            Some(())
+        }
     }
 }
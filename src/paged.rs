@@ -0,0 +1,587 @@
+//! A storage allocating its components in fixed-size pages.
+use std::{mem, ops::Range, slice};
+
+use crate::{
+    count_some,
+    hooks::{Hooks, StorageHooks},
+    Entity, ExactJoin, Join, Joinable, Joined, MemoryUsage,
+};
+
+/// The number of entities stored in each page of a [`PagedStorage`].
+///
+/// [`PagedStorage`]: struct.PagedStorage.html
+pub const PAGE_SIZE: usize = 256;
+
+type Pages<T> = Vec<Option<Box<Page<T>>>>;
+
+#[derive(Debug, Clone)]
+struct Page<T> {
+    slots: Box<[Option<T>]>,
+    /// The number of components in `slots`.
+    len: usize,
+}
+
+impl<T> Page<T> {
+    fn new() -> Self {
+        Page {
+            slots: (0..PAGE_SIZE).map(|_| None).collect(),
+            len: 0,
+        }
+    }
+}
+
+/// A storage which only allocates memory for pages of [`PAGE_SIZE`] entities
+/// which contain at least one component.
+///
+/// Use this instead of a [`Storage`] if the ids of the entities with this
+/// component are clustered but spread over a large range.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{paged::PagedStorage, Entity, Joinable, Storage};
+///
+/// let mut velocity = PagedStorage::new();
+/// velocity.insert(Entity(3), 1);
+/// velocity.insert(Entity(1_000_000), 2);
+///
+/// let mut position = Storage::new();
+/// position.insert(Entity(3), 10);
+///
+/// for (position, velocity) in (&mut position, &velocity).join() {
+///     *position += velocity;
+/// }
+/// assert_eq!(position.get(Entity(3)), Some(&11));
+/// assert_eq!(velocity.memory_usage().capacity, 512);
+/// ```
+///
+/// [`PAGE_SIZE`]: constant.PAGE_SIZE.html
+/// [`Storage`]: ../struct.Storage.html
+#[derive(Debug, Clone)]
+pub struct PagedStorage<T> {
+    pages: Pages<T>,
+    hooks: StorageHooks<T>,
+    /// The number of components in `pages`.
+    len: usize,
+}
+
+impl<T> Default for PagedStorage<T> {
+    fn default() -> Self {
+        PagedStorage::new()
+    }
+}
+
+impl<T> Drop for PagedStorage<T> {
+    fn drop(&mut self) {
+        // Only visit the remaining components if a hook wants them.
+        if self.hooks.on_remove() {
+            self.clear();
+        }
+    }
+}
+
+impl<T> PagedStorage<T> {
+    pub fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            hooks: StorageHooks::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of components in this storage.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the lifecycle hooks of this storage.
+    pub fn hooks_mut(&mut self) -> &mut Hooks<T> {
+        self.hooks.get_mut()
+    }
+
+    /// Removes all components in this storage, freeing all pages.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        for (i, page) in mem::take(&mut self.pages).into_iter().enumerate() {
+            for (j, c) in page
+                .into_iter()
+                .flat_map(|page| page.slots.into_vec())
+                .enumerate()
+            {
+                if let Some(c) = c {
+                    self.hooks.removed(Entity(i * PAGE_SIZE + j), &c);
+                }
+            }
+        }
+    }
+
+    /// Returns the component of the entity at `idx` in case it exists.
+    pub fn get(&self, idx: Entity) -> Option<&T> {
+        let page = self.pages.get(idx.0 / PAGE_SIZE)?.as_ref()?;
+        page.slots[idx.0 % PAGE_SIZE].as_ref()
+    }
+
+    pub fn get_mut(&mut self, idx: Entity) -> Option<&mut T> {
+        let page = self.pages.get_mut(idx.0 / PAGE_SIZE)?.as_mut()?;
+        page.slots[idx.0 % PAGE_SIZE].as_mut()
+    }
+
+    /// Inserts a component for the entity at `idx`.
+    ///
+    /// In case the component was already present the previous
+    /// one is returned.
+    pub fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        let page = idx.0 / PAGE_SIZE;
+        if page >= self.pages.len() {
+            self.pages.resize_with(page + 1, || None);
+        }
+
+        let page = self.pages[page].get_or_insert_with(|| Box::new(Page::new()));
+        let slot = &mut page.slots[idx.0 % PAGE_SIZE];
+        let previous = slot.replace(c);
+        let c = slot.as_ref().unwrap();
+        if let Some(ref previous) = previous {
+            self.hooks.replaced(idx, previous, c);
+        } else {
+            page.len += 1;
+            self.len += 1;
+            self.hooks.inserted(idx, c);
+        }
+        previous
+    }
+
+    /// Removes this component for the entity at `idx`.
+    ///
+    /// Pages are freed once their last component is removed.
+    pub fn remove(&mut self, idx: Entity) -> Option<T> {
+        let entry = self.pages.get_mut(idx.0 / PAGE_SIZE)?;
+        let page = entry.as_mut()?;
+        let c = page.slots[idx.0 % PAGE_SIZE].take()?;
+        page.len -= 1;
+        if page.len == 0 {
+            *entry = None;
+        }
+        self.len -= 1;
+        self.hooks.removed(idx, &c);
+        Some(c)
+    }
+
+    /// Removes all components of this storage.
+    ///
+    /// Like `Vec::drain`, all remaining components are removed
+    /// once the returned iterator is dropped.
+    pub fn drain(&mut self) -> PagedDrain<'_, T> {
+        PagedDrain {
+            storage: self,
+            position: 0,
+            ignored: 0,
+        }
+    }
+
+    /// Reserves space for at least `additional` more pages after
+    /// the ones this storage currently has slots for.
+    ///
+    /// This does not allocate the pages themselves, which only
+    /// happens once a component is inserted into them.
+    pub fn reserve(&mut self, additional: usize) {
+        self.pages.reserve(additional)
+    }
+
+    /// Frees all memory after the last allocated page.
+    pub fn shrink_to_fit(&mut self) {
+        let len = self
+            .pages
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |i| i + 1);
+        self.pages.truncate(len);
+        self.pages.shrink_to_fit();
+    }
+
+    /// Returns the amount of memory used by this storage.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let pages = self.pages.iter().filter(|page| page.is_some()).count();
+        MemoryUsage {
+            len: self.len,
+            capacity: pages * PAGE_SIZE,
+            bytes: self.pages.capacity() * mem::size_of::<Option<Box<Page<T>>>>()
+                + pages * (mem::size_of::<Page<T>>() + PAGE_SIZE * mem::size_of::<Option<T>>()),
+        }
+    }
+
+    /// The position after the component of the largest entity.
+    fn join_len(&self) -> usize {
+        self.pages
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, page)| {
+                let last = page.as_ref()?.slots.iter().rposition(Option::is_some)?;
+                Some(i * PAGE_SIZE + last + 1)
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// Returns the number of positions without a component before the next one,
+/// given the remaining slots of the current page and all following pages.
+fn may_skip<T>(
+    slots: &[Option<T>],
+    until_page_end: usize,
+    pages: &[Option<Box<Page<T>>>],
+) -> usize {
+    if let Some(i) = slots.iter().position(Option::is_some) {
+        return i;
+    }
+
+    let mut skip = until_page_end;
+    for page in pages {
+        match page
+            .as_ref()
+            .and_then(|page| page.slots.iter().position(Option::is_some))
+        {
+            Some(i) => return skip + i,
+            None => skip += PAGE_SIZE,
+        }
+    }
+    usize::MAX
+}
+
+/// Returns the number of positions without a component starting at `position`.
+fn skip_from<T>(pages: &[Option<Box<Page<T>>>], position: usize) -> usize {
+    let page = position / PAGE_SIZE;
+    let slots = match pages.get(page) {
+        Some(Some(page)) => &page.slots[position % PAGE_SIZE..],
+        _ => &[],
+    };
+    let rest = pages.get(page + 1..).unwrap_or(&[]);
+    may_skip(slots, PAGE_SIZE - position % PAGE_SIZE, rest)
+}
+
+/// Returns the number of components in `range`, given the pages starting at page `first`.
+fn count_range<T>(pages: &[Option<Box<Page<T>>>], first: usize, range: Range<usize>) -> usize {
+    let mut count = 0;
+    for (i, page) in pages.iter().enumerate() {
+        let start = (first + i) * PAGE_SIZE;
+        if start >= range.end {
+            break;
+        } else if let Some(page) = page {
+            let from = range.start.saturating_sub(start).min(PAGE_SIZE);
+            let to = (range.end - start).min(PAGE_SIZE);
+            count += if from == 0 && to == PAGE_SIZE {
+                page.len
+            } else {
+                count_some(&page.slots[from..to.max(from)])
+            };
+        }
+    }
+    count
+}
+
+/// The iterator created by joining a `&PagedStorage`.
+pub struct PagedIter<'a, T> {
+    pages: &'a [Option<Box<Page<T>>>],
+    position: usize,
+    /// The position after the last remaining one.
+    end: usize,
+    /// The number of components between `position` and `end`.
+    len: usize,
+}
+
+impl<'a, T> Clone for PagedIter<'a, T> {
+    fn clone(&self) -> Self {
+        PagedIter {
+            pages: self.pages,
+            position: self.position,
+            end: self.end,
+            len: self.len,
+        }
+    }
+}
+
+impl<'a, T> PagedIter<'a, T> {
+    /// The remaining slots of the page containing `position`.
+    fn slots(&self) -> &'a [Option<T>] {
+        match self.pages.get(self.position / PAGE_SIZE) {
+            Some(Some(page)) => &page.slots[self.position % PAGE_SIZE..],
+            _ => &[],
+        }
+    }
+}
+
+impl<'a, T> Join for PagedIter<'a, T> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.position = curr;
+        skip_from(self.pages, curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        // Recounted, as `seek` only keeps an upper bound in `len`.
+        self.end = self.end.min(self.position.saturating_add(len));
+        self.len = count_range(self.pages, 0, self.position..self.end);
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.position = self.position.saturating_add(n);
+    }
+}
+
+impl<'a, T> ExactJoin for PagedIter<'a, T> {}
+
+impl<'a, T> Iterator for PagedIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        let target = self.position.saturating_add(n);
+        while self.position < target && self.len > 0 {
+            // Count the skipped components one page at a time.
+            let slots = self.slots();
+            let skipped = slots.len().min(target - self.position);
+            self.len -= count_some(&slots[..skipped]);
+            self.position += (PAGE_SIZE - self.position % PAGE_SIZE).min(target - self.position);
+        }
+
+        self.position = target;
+        let item = self.slots().first().and_then(Option::as_ref);
+        if item.is_some() {
+            self.len -= 1;
+        }
+        self.position = self.position.saturating_add(1);
+        item
+    }
+}
+
+impl<'a, T> Joinable for &'a PagedStorage<T> {
+    type Joined = PagedIter<'a, T>;
+    type Item = &'a T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        Joined::new(
+            PagedIter {
+                pages: &self.pages,
+                position: 0,
+                end: usize::MAX,
+                len: self.len,
+            },
+            self.join_len(),
+        )
+    }
+
+    fn fetch(self, entity: Entity) -> Option<&'a T> {
+        self.get(entity)
+    }
+}
+
+/// The iterator created by joining a `&mut PagedStorage`.
+pub struct PagedIterMut<'a, T> {
+    /// The pages after the current one.
+    pages: slice::IterMut<'a, Option<Box<Page<T>>>>,
+    /// The index of the current page.
+    page: usize,
+    /// The remaining slots of the current page, starting at `position`.
+    slots: &'a mut [Option<T>],
+    position: usize,
+    /// The position after the last remaining one.
+    end: usize,
+    /// The number of components between `position` and `end`.
+    len: usize,
+}
+
+impl<'a, T> PagedIterMut<'a, T> {
+    /// Moves to the next page.
+    fn next_page(&mut self) {
+        self.len -= count_some(self.slots);
+        self.page += 1;
+        self.slots = match self.pages.next() {
+            Some(Some(page)) => &mut page.slots[..],
+            _ => &mut [],
+        };
+    }
+}
+
+impl<'a, T> Join for PagedIterMut<'a, T> {
+    fn may_skip(&mut self, _curr: usize) -> usize {
+        let until_page_end = ((self.page + 1) * PAGE_SIZE).saturating_sub(self.position);
+        may_skip(self.slots, until_page_end, self.pages.as_slice())
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        // Recounted, as `seek` only keeps an upper bound in `len`.
+        self.end = self.end.min(self.position.saturating_add(len));
+        let in_page = self.end.saturating_sub(self.position).min(self.slots.len());
+        let pages = self.pages.as_slice();
+        self.len = count_some(&self.slots[..in_page])
+            + count_range(pages, self.page + 1, self.position..self.end);
+    }
+
+    fn seek(&mut self, n: usize) {
+        let target = self.position.saturating_add(n);
+        let page = target / PAGE_SIZE;
+        if page > self.page {
+            self.slots = match self.pages.nth(page - self.page - 1) {
+                Some(Some(page)) => &mut page.slots[..],
+                _ => &mut [],
+            };
+            self.page = page;
+            self.position = page * PAGE_SIZE;
+        }
+
+        let slots = mem::take(&mut self.slots);
+        self.slots = slots.get_mut(target - self.position..).unwrap_or_default();
+        self.position = target;
+    }
+}
+
+impl<'a, T> ExactJoin for PagedIterMut<'a, T> {}
+
+impl<'a, T> Iterator for PagedIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a mut T> {
+        let target = self.position.saturating_add(n);
+        while target / PAGE_SIZE > self.page && self.len > 0 {
+            self.next_page();
+            self.position = self.page * PAGE_SIZE;
+        }
+
+        let slots = mem::take(&mut self.slots);
+        let offset = target - self.position;
+        self.position = target.saturating_add(1);
+        if offset < slots.len() {
+            let (skipped, rest) = slots.split_at_mut(offset);
+            let (first, rest) = rest.split_first_mut().unwrap();
+            self.len -= count_some(skipped);
+            self.slots = rest;
+            let item = first.as_mut();
+            if item.is_some() {
+                self.len -= 1;
+            }
+            item
+        } else {
+            self.len -= count_some(slots);
+            None
+        }
+    }
+}
+
+impl<'a, T> Joinable for &'a mut PagedStorage<T> {
+    type Joined = PagedIterMut<'a, T>;
+    type Item = &'a mut T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let len = self.join_len();
+        let mut pages = self.pages.iter_mut();
+        let slots = match pages.next() {
+            Some(Some(page)) => &mut page.slots[..],
+            _ => &mut [],
+        };
+        Joined::new(
+            PagedIterMut {
+                pages,
+                page: 0,
+                slots,
+                position: 0,
+                end: usize::MAX,
+                len: self.len,
+            },
+            len,
+        )
+    }
+
+    fn fetch(self, entity: Entity) -> Option<&'a mut T> {
+        self.get_mut(entity)
+    }
+}
+
+/// The iterator returned by `PagedStorage::drain`.
+///
+/// Like `Vec::drain`, all remaining components are removed once
+/// this is dropped, even if it was not fully consumed.
+pub struct PagedDrain<'a, T> {
+    storage: &'a mut PagedStorage<T>,
+    position: usize,
+    /// The number of components which are removed without being yielded after `truncate`.
+    ignored: usize,
+}
+
+impl<'a, T> Drop for PagedDrain<'a, T> {
+    fn drop(&mut self) {
+        self.storage.clear();
+    }
+}
+
+impl<'a, T> Join for PagedDrain<'a, T> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        skip_from(&self.storage.pages, curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        // All components before the current position have already been removed.
+        let len = self.storage.len() - self.ignored;
+        (len, Some(len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let end = self.position.saturating_add(len);
+        let ignored = count_range(&self.storage.pages, 0, end..usize::MAX);
+        self.ignored = self.ignored.max(ignored);
+    }
+}
+
+impl<'a, T> ExactJoin for PagedDrain<'a, T> {}
+
+impl<'a, T> Iterator for PagedDrain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let item = self.storage.remove(Entity(self.position));
+        self.position += 1;
+        item
+    }
+
+    fn nth(&mut self, n: usize) -> Option<T> {
+        let end = self.position.saturating_add(n);
+        while self.position < end && !self.storage.is_empty() {
+            match self.storage.pages.get(self.position / PAGE_SIZE) {
+                Some(Some(_)) => {
+                    self.storage.remove(Entity(self.position));
+                    self.position += 1;
+                }
+                Some(None) => self.position = (self.position / PAGE_SIZE + 1) * PAGE_SIZE,
+                None => break,
+            }
+        }
+        self.position = end;
+        self.next()
+    }
+}
+
+impl<'a, T> Joinable for PagedDrain<'a, T> {
+    type Joined = PagedDrain<'a, T>;
+    type Item = T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let (len, position) = (self.storage.join_len(), self.position);
+        Joined::starting_at(self, position, len)
+    }
+}
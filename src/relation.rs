@@ -0,0 +1,311 @@
+//! Directed edges between entities.
+use std::collections::{btree_map, btree_set, BTreeMap, BTreeSet};
+
+use crate::{Entity, ExactJoin, Join, Joinable, Joined, SparseIter};
+
+/// Stores directed edges between entities, each with a payload of type `R`.
+///
+/// Both the targets of an entity and the sources pointing at an entity can be
+/// looked up efficiently, and joined with other storages using
+/// [`forward`] and [`reverse`] respectively.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{relation::Relation, Entity, Joinable, Storage};
+///
+/// let (ship, station, other) = (Entity(0), Entity(1), Entity(2));
+///
+/// let mut docked_at = Relation::new();
+/// docked_at.insert(ship, station, ());
+/// docked_at.insert(other, station, ());
+///
+/// let mut names = Storage::new();
+/// names.insert(station, "Station");
+///
+/// for (name, sources) in (&names, docked_at.reverse()).join() {
+///     assert_eq!(*name, "Station");
+///     assert_eq!(sources.collect::<Vec<_>>(), vec![ship, other]);
+/// }
+///
+/// docked_at.remove_entity(station);
+/// assert!(docked_at.is_empty());
+/// ```
+///
+/// [`forward`]: struct.Relation.html#method.forward
+/// [`reverse`]: struct.Relation.html#method.reverse
+#[derive(Debug, Clone)]
+pub struct Relation<R = ()> {
+    forward: BTreeMap<usize, BTreeMap<usize, R>>,
+    reverse: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl<R> Default for Relation<R> {
+    fn default() -> Self {
+        Relation::new()
+    }
+}
+
+impl<R> Relation<R> {
+    /// Creates a new `Relation` without any edges.
+    pub fn new() -> Self {
+        Self {
+            forward: BTreeMap::new(),
+            reverse: BTreeMap::new(),
+        }
+    }
+
+    /// Removes all edges of this relation.
+    pub fn clear(&mut self) {
+        self.forward.clear();
+        self.reverse.clear();
+    }
+
+    /// Returns the number of edges in this relation.
+    pub fn len(&self) -> usize {
+        self.forward.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    /// Returns `true` if there is an edge from `from` to `to`.
+    pub fn contains(&self, from: Entity, to: Entity) -> bool {
+        self.get(from, to).is_some()
+    }
+
+    /// Returns the payload of the edge from `from` to `to` in case it exists.
+    pub fn get(&self, from: Entity, to: Entity) -> Option<&R> {
+        self.forward.get(&from.0).and_then(|t| t.get(&to.0))
+    }
+
+    pub fn get_mut(&mut self, from: Entity, to: Entity) -> Option<&mut R> {
+        self.forward.get_mut(&from.0).and_then(|t| t.get_mut(&to.0))
+    }
+
+    /// Adds an edge from `from` to `to`.
+    ///
+    /// In case this edge was already present the previous
+    /// payload is returned.
+    pub fn insert(&mut self, from: Entity, to: Entity, payload: R) -> Option<R> {
+        self.reverse.entry(to.0).or_default().insert(from.0);
+        self.forward
+            .entry(from.0)
+            .or_default()
+            .insert(to.0, payload)
+    }
+
+    /// Removes the edge from `from` to `to`.
+    pub fn remove(&mut self, from: Entity, to: Entity) -> Option<R> {
+        let targets = self.forward.get_mut(&from.0)?;
+        let payload = targets.remove(&to.0)?;
+        if targets.is_empty() {
+            self.forward.remove(&from.0);
+        }
+
+        let sources = self.reverse.get_mut(&to.0).expect("missing reverse edge");
+        sources.remove(&from.0);
+        if sources.is_empty() {
+            self.reverse.remove(&to.0);
+        }
+        Some(payload)
+    }
+
+    /// Removes all edges starting or ending at `entity`.
+    pub fn remove_entity(&mut self, entity: Entity) {
+        if let Some(targets) = self.forward.remove(&entity.0) {
+            for to in targets.keys() {
+                let sources = self.reverse.get_mut(to).expect("missing reverse edge");
+                sources.remove(&entity.0);
+                if sources.is_empty() {
+                    self.reverse.remove(to);
+                }
+            }
+        }
+
+        if let Some(sources) = self.reverse.remove(&entity.0) {
+            for from in sources {
+                let targets = self.forward.get_mut(&from).expect("missing edge");
+                targets.remove(&entity.0);
+                if targets.is_empty() {
+                    self.forward.remove(&from);
+                }
+            }
+        }
+    }
+
+    /// Iterates over all edges starting at `from`.
+    pub fn targets(&self, from: Entity) -> Targets<'_, R> {
+        Targets(self.forward.get(&from.0).map(BTreeMap::iter))
+    }
+
+    /// Iterates over all entities with an edge to `to`.
+    pub fn sources(&self, to: Entity) -> Sources<'_> {
+        Sources(self.reverse.get(&to.0).map(BTreeSet::iter))
+    }
+
+    /// Joins over the sources of all edges, yielding their [`Targets`].
+    ///
+    /// [`Targets`]: struct.Targets.html
+    pub fn forward(&self) -> Forward<'_, R> {
+        Forward(self)
+    }
+
+    /// Joins over the targets of all edges, yielding their [`Sources`].
+    ///
+    /// [`Sources`]: struct.Sources.html
+    pub fn reverse(&self) -> Reverse<'_, R> {
+        Reverse(self)
+    }
+}
+
+/// An iterator over the targets of an entity and the payload of each edge.
+#[derive(Debug, Clone)]
+pub struct Targets<'a, R>(Option<btree_map::Iter<'a, usize, R>>);
+
+impl<'a, R> Iterator for Targets<'a, R> {
+    type Item = (Entity, &'a R);
+
+    fn next(&mut self) -> Option<(Entity, &'a R)> {
+        self.0.as_mut()?.next().map(|(&k, v)| (Entity(k), v))
+    }
+}
+
+/// An iterator over all entities with an edge to a given entity.
+#[derive(Debug, Clone)]
+pub struct Sources<'a>(Option<btree_set::Iter<'a, usize>>);
+
+impl<'a> Iterator for Sources<'a> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        self.0.as_mut()?.next().map(|&k| Entity(k))
+    }
+}
+
+/// The source side of a [`Relation`], created by [`Relation::forward`].
+///
+/// [`Relation`]: struct.Relation.html
+/// [`Relation::forward`]: struct.Relation.html#method.forward
+pub struct Forward<'a, R>(&'a Relation<R>);
+
+/// The iterator created by [`Forward::join`].
+///
+/// [`Forward::join`]: struct.Forward.html
+pub struct ForwardIter<'a, R>(SparseIter<'a, BTreeMap<usize, R>>);
+
+impl<'a, R> Join for ForwardIter<'a, R> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.0.may_skip(curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        self.0.remaining()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.0.seek(n)
+    }
+}
+
+impl<'a, R> ExactJoin for ForwardIter<'a, R> {}
+
+impl<'a, R> Iterator for ForwardIter<'a, R> {
+    type Item = Targets<'a, R>;
+
+    fn next(&mut self) -> Option<Targets<'a, R>> {
+        self.0.next().map(|t| Targets(Some(t.iter())))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Targets<'a, R>> {
+        self.0.nth(n).map(|t| Targets(Some(t.iter())))
+    }
+}
+
+impl<'a, R> Joinable for Forward<'a, R> {
+    type Joined = ForwardIter<'a, R>;
+    type Item = Targets<'a, R>;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let inner = &self.0.forward;
+        Joined::new(
+            ForwardIter(SparseIter::new(inner)),
+            inner.keys().next_back().map_or(0, |v| v + 1),
+        )
+    }
+
+    fn fetch(self, entity: Entity) -> Option<Targets<'a, R>> {
+        self.0
+            .forward
+            .get(&entity.0)
+            .map(|t| Targets(Some(t.iter())))
+    }
+}
+
+/// The target side of a [`Relation`], created by [`Relation::reverse`].
+///
+/// [`Relation`]: struct.Relation.html
+/// [`Relation::reverse`]: struct.Relation.html#method.reverse
+pub struct Reverse<'a, R>(&'a Relation<R>);
+
+/// The iterator created by [`Reverse::join`].
+///
+/// [`Reverse::join`]: struct.Reverse.html
+pub struct ReverseIter<'a>(SparseIter<'a, BTreeSet<usize>>);
+
+impl<'a> Join for ReverseIter<'a> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.0.may_skip(curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        self.0.remaining()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.0.seek(n)
+    }
+}
+
+impl<'a> ExactJoin for ReverseIter<'a> {}
+
+impl<'a> Iterator for ReverseIter<'a> {
+    type Item = Sources<'a>;
+
+    fn next(&mut self) -> Option<Sources<'a>> {
+        self.0.next().map(|s| Sources(Some(s.iter())))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Sources<'a>> {
+        self.0.nth(n).map(|s| Sources(Some(s.iter())))
+    }
+}
+
+impl<'a, R> Joinable for Reverse<'a, R> {
+    type Joined = ReverseIter<'a>;
+    type Item = Sources<'a>;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let inner = &self.0.reverse;
+        Joined::new(
+            ReverseIter(SparseIter::new(inner)),
+            inner.keys().next_back().map_or(0, |v| v + 1),
+        )
+    }
+
+    fn fetch(self, entity: Entity) -> Option<Sources<'a>> {
+        self.0
+            .reverse
+            .get(&entity.0)
+            .map(|s| Sources(Some(s.iter())))
+    }
+}
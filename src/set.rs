@@ -0,0 +1,195 @@
+use std::{
+    collections::{btree_set, BTreeSet},
+    iter::{Copied, FromIterator, Peekable},
+};
+
+use crate::{Entity, ExactJoin, Join, Joinable, Joined};
+
+/// An ordered set of entities which can be used in a `join`.
+///
+/// Joining an `EntitySet` yields the contained entities,
+/// so it can be used to restrict a join to a given selection.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{set::EntitySet, Entity, Joinable, Storage};
+///
+/// let mut names = Storage::new();
+/// names.insert(Entity(0), "Foo");
+/// names.insert(Entity(1), "Bar");
+/// names.insert(Entity(2), "Baz");
+///
+/// let selected: EntitySet = vec![Entity(2), Entity(0)].into_iter().collect();
+/// let joined: Vec<_> = (&names, &selected).join().collect();
+/// assert_eq!(joined, vec![(&"Foo", Entity(0)), (&"Baz", Entity(2))]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntitySet {
+    inner: BTreeSet<usize>,
+}
+
+impl EntitySet {
+    /// Creates an empty `EntitySet`.
+    pub const fn new() -> Self {
+        Self {
+            inner: BTreeSet::new(),
+        }
+    }
+
+    /// Adds `entity` to this set, returning `false` if it was already present.
+    pub fn insert(&mut self, entity: Entity) -> bool {
+        self.inner.insert(entity.0)
+    }
+
+    /// Removes `entity` from this set, returning `false` if it was not present.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        self.inner.remove(&entity.0)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.inner.contains(&entity.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Iterates over all entities in this set in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.inner.iter().map(|&e| Entity(e))
+    }
+
+    fn join_len(&self) -> usize {
+        self.inner.iter().next_back().map_or(0, |v| v + 1)
+    }
+}
+
+impl FromIterator<Entity> for EntitySet {
+    fn from_iter<I: IntoIterator<Item = Entity>>(iter: I) -> Self {
+        Self {
+            inner: iter.into_iter().map(|e| e.0).collect(),
+        }
+    }
+}
+
+impl Extend<Entity> for EntitySet {
+    fn extend<I: IntoIterator<Item = Entity>>(&mut self, iter: I) {
+        self.inner.extend(iter.into_iter().map(|e| e.0))
+    }
+}
+
+/// The iterator created by joining an [`EntitySet`].
+///
+/// [`EntitySet`]: struct.EntitySet.html
+#[derive(Debug, Clone)]
+pub struct EntitySetIter<I: Iterator<Item = usize>> {
+    inner: Peekable<I>,
+    /// The last remaining entity, in case it has already been taken from the back of `inner`.
+    back: Option<usize>,
+    position: usize,
+}
+
+impl<I: Iterator<Item = usize>> EntitySetIter<I> {
+    fn new(inner: I) -> Self {
+        Self {
+            inner: inner.peekable(),
+            back: None,
+            position: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<usize> {
+        self.inner.peek().copied().or(self.back)
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        self.inner.next().or_else(|| self.back.take())
+    }
+}
+
+impl<I: ExactSizeIterator<Item = usize> + DoubleEndedIterator> Join for EntitySetIter<I> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.position = curr;
+        while self.peek().is_some_and(|k| k < curr) {
+            self.pop();
+        }
+
+        self.peek().map_or(usize::MAX, |k| k - curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        let len = self.inner.len() + usize::from(self.back.is_some());
+        (len, Some(len))
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let end = self.position.saturating_add(len);
+        if self.back.is_some_and(|k| k >= end) {
+            self.back = None;
+        }
+        if self.back.is_none() {
+            self.back = self.inner.rfind(|&k| k < end);
+        }
+    }
+}
+
+impl<I: ExactSizeIterator<Item = usize> + DoubleEndedIterator> ExactJoin for EntitySetIter<I> {}
+
+impl<I: Iterator<Item = usize>> Iterator for EntitySetIter<I> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let position = self.position;
+        while self.peek().is_some_and(|k| k < position) {
+            self.pop();
+        }
+
+        let item = if self.peek() == Some(position) {
+            self.pop().map(Entity)
+        } else {
+            None
+        };
+        self.position += 1;
+        item
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Entity> {
+        self.position += n;
+        self.next()
+    }
+}
+
+impl Joinable for EntitySet {
+    type Joined = EntitySetIter<btree_set::IntoIter<usize>>;
+    type Item = Entity;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let len = self.join_len();
+        Joined::new(EntitySetIter::new(self.inner.into_iter()), len)
+    }
+
+    fn fetch(self, entity: Entity) -> Option<Entity> {
+        Some(entity).filter(|&e| self.contains(e))
+    }
+}
+
+impl<'a> Joinable for &'a EntitySet {
+    type Joined = EntitySetIter<Copied<btree_set::Iter<'a, usize>>>;
+    type Item = Entity;
+
+    fn join(self) -> Joined<Self::Joined> {
+        Joined::new(
+            EntitySetIter::new(self.inner.iter().copied()),
+            self.join_len(),
+        )
+    }
+
+    fn fetch(self, entity: Entity) -> Option<Entity> {
+        Some(entity).filter(|&e| self.contains(e))
+    }
+}
@@ -0,0 +1,312 @@
+//! A storage sharing equal components between entities.
+use std::{
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use crate::{
+    drain::{Drain, OnRemove},
+    DoubleEndedJoin, Entity, ExactJoin, Iter, Join, Joinable, Joined, Storage, WithEntities,
+};
+
+/// A storage which only stores each distinct component once, no matter
+/// how many entities have it.
+///
+/// Mutating a component only affects the given entity, cloning
+/// the component in case it is shared with other entities.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{shared::SharedStorage, Entity, Joinable};
+///
+/// let mut sprites = SharedStorage::new();
+/// for i in 0..100 {
+///     sprites.insert(Entity(i), vec!["idle.png"; 64]);
+/// }
+/// assert_eq!(sprites.unique_len(), 1);
+///
+/// sprites.get_mut(Entity(7)).unwrap()[0] = "hurt.png";
+/// assert_eq!(sprites.unique_len(), 2);
+/// assert_eq!(sprites.get(Entity(8)).unwrap()[0], "idle.png");
+///
+/// let hurt = (&sprites).join().filter(|sprite| sprite[0] == "hurt.png").count();
+/// assert_eq!(hurt, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedStorage<T> {
+    storage: Storage<Arc<T>>,
+    /// Each distinct component of `storage`, together with the number of entities using it.
+    values: BTreeMap<Arc<T>, usize>,
+}
+
+impl<T: Ord + Clone> Default for SharedStorage<T> {
+    fn default() -> Self {
+        SharedStorage::new()
+    }
+}
+
+impl<T: Ord + Clone> SharedStorage<T> {
+    /// Creates a new `SharedStorage`.
+    pub fn new() -> Self {
+        Self {
+            storage: Storage::new(),
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of components in this storage.
+    pub fn len(&self) -> usize {
+        self.storage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.storage.is_empty()
+    }
+
+    /// Returns the number of distinct components in this storage.
+    pub fn unique_len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Removes all components in this storage.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+        self.values.clear();
+    }
+
+    /// Returns the component of the entity at `idx` in case it exists.
+    pub fn get(&self, idx: Entity) -> Option<&T> {
+        self.storage.get(idx).map(Arc::as_ref)
+    }
+
+    /// Returns the shared component of the entity at `idx`.
+    pub fn get_shared(&self, idx: Entity) -> Option<&Arc<T>> {
+        self.storage.get(idx)
+    }
+
+    /// Mutably accesses the component of the entity at `idx`,
+    /// cloning it if it is shared with other entities.
+    ///
+    /// The component is shared again once the returned guard is dropped.
+    pub fn get_mut(&mut self, idx: Entity) -> Option<SharedRefMut<'_, T>> {
+        let component = self.storage.get_mut(idx)?;
+        unuse(&mut self.values, component);
+        Some(SharedRefMut {
+            component,
+            values: &mut self.values,
+        })
+    }
+
+    /// Inserts a component for the entity at `idx`.
+    ///
+    /// In case the component was already present the previous
+    /// one is returned.
+    pub fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        let c = intern(&mut self.values, Arc::new(c));
+        let previous = self.storage.insert(idx, c)?;
+        Some(self.release(previous))
+    }
+
+    /// Removes this component for the entity at `idx`.
+    pub fn remove(&mut self, idx: Entity) -> Option<T> {
+        let c = self.storage.remove(idx)?;
+        Some(self.release(c))
+    }
+
+    /// Removes all components of this storage.
+    pub fn drain(&mut self) -> SharedDrain<'_, T> {
+        SharedDrain(self.storage.drain_with(Unuse(&mut self.values)))
+    }
+
+    /// Takes a component which was removed from `storage`, cloning it if it is still shared.
+    fn release(&mut self, c: Arc<T>) -> T {
+        unuse(&mut self.values, &c);
+        Arc::try_unwrap(c).unwrap_or_else(|c| T::clone(&c))
+    }
+}
+
+/// Returns the shared component equal to `c`, adding `c` if there is none.
+fn intern<T: Ord>(values: &mut BTreeMap<Arc<T>, usize>, c: Arc<T>) -> Arc<T> {
+    match values.get_key_value(&*c) {
+        Some((shared, _)) => {
+            let shared = shared.clone();
+            *values.get_mut(&*c).unwrap() += 1;
+            shared
+        }
+        None => {
+            values.insert(c.clone(), 1);
+            c
+        }
+    }
+}
+
+/// Removes one use of `c`, forgetting it once it is no longer used.
+/// Removes the uses of the components drained from a `SharedStorage`.
+struct Unuse<'a, T>(&'a mut BTreeMap<Arc<T>, usize>);
+
+impl<'a, T: Ord> OnRemove<Arc<T>> for Unuse<'a, T> {
+    fn removed(&mut self, _idx: Entity, c: &Arc<T>) {
+        unuse(self.0, c)
+    }
+}
+
+fn unuse<T: Ord>(values: &mut BTreeMap<Arc<T>, usize>, c: &T) {
+    let uses = values.get_mut(c).expect("missing shared component");
+    *uses -= 1;
+    if *uses == 0 {
+        values.remove(c);
+    }
+}
+
+/// A mutable reference to a component of a [`SharedStorage`].
+///
+/// The component is shared with all entities with
+/// an equal component once this guard is dropped.
+///
+/// [`SharedStorage`]: struct.SharedStorage.html
+pub struct SharedRefMut<'a, T: Ord> {
+    component: &'a mut Arc<T>,
+    values: &'a mut BTreeMap<Arc<T>, usize>,
+}
+
+impl<'a, T: Ord> Deref for SharedRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.component
+    }
+}
+
+impl<'a, T: Ord + Clone> DerefMut for SharedRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        Arc::make_mut(self.component)
+    }
+}
+
+impl<'a, T: Ord> Drop for SharedRefMut<'a, T> {
+    fn drop(&mut self) {
+        *self.component = intern(self.values, self.component.clone());
+    }
+}
+
+/// The iterator created by joining a `&SharedStorage`.
+#[derive(Clone)]
+pub struct SharedIter<'a, T>(Iter<'a, Arc<T>>);
+
+impl<'a, T> Iterator for SharedIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a T> {
+        self.0.nth(n).map(Arc::as_ref)
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for SharedIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<&'a T> {
+        self.0.nth_back(n).map(Arc::as_ref)
+    }
+}
+
+impl<'a, T> Join for SharedIter<'a, T> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.0.may_skip(curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        self.0.remaining()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.0.seek(n)
+    }
+}
+
+impl<'a, T> ExactJoin for SharedIter<'a, T> {}
+
+impl<'a, T> DoubleEndedJoin for SharedIter<'a, T> {
+    fn may_skip_back(&mut self) -> usize {
+        self.0.may_skip_back()
+    }
+}
+
+impl<'a, T> Joinable for &'a SharedStorage<T> {
+    type Joined = SharedIter<'a, T>;
+    type Item = &'a T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let joined = (&self.storage).join();
+        Joined::new(SharedIter(joined.iter), joined.len)
+    }
+
+    fn fetch(self, entity: Entity) -> Option<&'a T> {
+        self.storage.get(entity).map(Arc::as_ref)
+    }
+}
+
+/// The iterator returned by `SharedStorage::drain`.
+///
+/// Like `Vec::drain`, all remaining components are removed once
+/// this is dropped, even if it was not fully consumed.
+pub struct SharedDrain<'a, T: Ord>(Drain<'a, Arc<T>, Unuse<'a, T>>);
+
+impl<'a, T: Ord + Clone> SharedDrain<'a, T> {
+    /// Yields the `Entity` of each removed component alongside it.
+    ///
+    /// This is a shorthand for `self.join().with_entities()`.
+    pub fn with_entities(self) -> WithEntities<Self> {
+        self.join().with_entities()
+    }
+}
+
+impl<'a, T: Ord> Join for SharedDrain<'a, T> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        self.0.may_skip(curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        self.0.remaining()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+}
+
+impl<'a, T: Ord> ExactJoin for SharedDrain<'a, T> {}
+
+impl<'a, T: Ord + Clone> Iterator for SharedDrain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<T> {
+        let c = self.0.nth(n)?;
+        Some(Arc::try_unwrap(c).unwrap_or_else(|c| T::clone(&c)))
+    }
+}
+
+impl<'a, T: Ord + Clone> Joinable for SharedDrain<'a, T> {
+    type Joined = SharedDrain<'a, T>;
+    type Item = T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        let joined = self.0.join();
+        Joined::starting_at(SharedDrain(joined.iter), joined.pos, joined.len)
+    }
+}
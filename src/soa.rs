@@ -0,0 +1,597 @@
+//! Storing each field of a component in its own contiguous array.
+//!
+//! See the [`soa_storage!`] macro for more details.
+//!
+//! [`soa_storage!`]: ../macro.soa_storage.html
+use std::{mem, ops::Range, slice};
+
+use crate::{chunk::ChunkJoin, ExactJoin, Join, Joinable, Joined};
+
+/// Defines a component together with a struct-of-arrays storage for it.
+///
+/// The given module contains the component itself and the following items:
+///
+/// - `Storage`, storing each field in a separate `Vec`.
+/// - `Ref<'a>` and `Mut<'a>`, views containing a reference to each field,
+///   which are yielded when joining `&Storage` and `&mut Storage`.
+/// - `Columns<'a>` and `ColumnsMut<'a>`, returned by `Storage::columns` and
+///   `Storage::columns_mut`, containing a joinable [`Column`] or [`ColumnMut`]
+///   for each field, so loops only access the fields they need.
+/// - `Iter<'a>` and `IterMut<'a>`, the iterators used when joining the storage.
+///
+/// All field types must implement `Default`, which is used for empty slots.
+///
+/// As the fields of a component are stored separately, `Storage::get` returns
+/// a `Ref` instead of a reference to the component, which is why the storage
+/// does not implement [`ComponentStorage`].
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{soa_storage, Entity, Joinable, Storage};
+///
+/// soa_storage! {
+///     pub mod transform {
+///         #[derive(Debug, Clone, PartialEq)]
+///         pub struct Transform {
+///             pub x: f32,
+///             pub y: f32,
+///             pub rotation: f32,
+///         }
+///     }
+/// }
+///
+/// use transform::Transform;
+///
+/// let mut transforms = transform::Storage::new();
+/// transforms.insert(Entity(0), Transform { x: 0.0, y: 0.0, rotation: 0.0 });
+/// transforms.insert(Entity(2), Transform { x: 1.0, y: 2.0, rotation: 0.5 });
+///
+/// let mut velocity = Storage::new();
+/// velocity.insert(Entity(2), (3.0, 4.0));
+///
+/// // only touch `x` and `y`
+/// let columns = transforms.columns_mut();
+/// for (x, y, &(vx, vy)) in (columns.x, columns.y, &velocity).join() {
+///     *x += vx;
+///     *y += vy;
+/// }
+///
+/// for view in (&mut transforms).join() {
+///     *view.rotation += 1.0;
+/// }
+///
+/// assert_eq!(
+///     transforms.remove(Entity(2)),
+///     Some(Transform { x: 4.0, y: 6.0, rotation: 1.5 })
+/// );
+/// ```
+///
+/// [`Column`]: soa/struct.Column.html
+/// [`ColumnMut`]: soa/struct.ColumnMut.html
+/// [`ComponentStorage`]: component/trait.ComponentStorage.html
+#[macro_export]
+macro_rules! soa_storage {
+    (
+        $(#[$mod_attr:meta])*
+        $mod_vis:vis mod $module:ident {
+            $(#[$attr:meta])*
+            $vis:vis struct $name:ident {
+                $($(#[$field_attr:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+            }
+        }
+    ) => {
+        $(#[$mod_attr])*
+        $mod_vis mod $module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $(#[$attr])*
+            $vis struct $name {
+                $($(#[$field_attr])* $field_vis $field: $ty),*
+            }
+
+            /// A storage keeping each field in a separate array.
+            #[derive(Default)]
+            pub struct Storage {
+                present: ::std::vec::Vec<bool>,
+                len: usize,
+                $($field: ::std::vec::Vec<$ty>),*
+            }
+
+            /// References to the fields of a component.
+            pub struct Ref<'a> {
+                $(pub $field: &'a $ty),*
+            }
+
+            /// Mutable references to the fields of a component.
+            pub struct Mut<'a> {
+                $(pub $field: &'a mut $ty),*
+            }
+
+            /// A joinable column for each field, returned by `Storage::columns`.
+            pub struct Columns<'a> {
+                $(pub $field: $crate::soa::Column<'a, $ty>),*
+            }
+
+            /// A mutable joinable column for each field, returned by `Storage::columns_mut`.
+            pub struct ColumnsMut<'a> {
+                $(pub $field: $crate::soa::ColumnMut<'a, $ty>),*
+            }
+
+            impl Storage {
+                pub fn new() -> Self {
+                    Self::default()
+                }
+
+                /// Returns the number of components in this storage.
+                pub fn len(&self) -> usize {
+                    self.len
+                }
+
+                pub fn is_empty(&self) -> bool {
+                    self.len == 0
+                }
+
+                /// Removes all components in this storage.
+                pub fn clear(&mut self) {
+                    for i in 0..self.present.len() {
+                        self.remove($crate::Entity(i));
+                    }
+                }
+
+                /// Returns the component of the entity at `idx` in case it exists.
+                pub fn get(&self, idx: $crate::Entity) -> ::std::option::Option<Ref<'_>> {
+                    if *self.present.get(idx.0)? {
+                        ::std::option::Option::Some(Ref {
+                            $($field: &self.$field[idx.0]),*
+                        })
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+
+                pub fn get_mut(&mut self, idx: $crate::Entity) -> ::std::option::Option<Mut<'_>> {
+                    if *self.present.get(idx.0)? {
+                        ::std::option::Option::Some(Mut {
+                            $($field: &mut self.$field[idx.0]),*
+                        })
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+
+                /// Inserts a component for the entity at `idx`.
+                ///
+                /// In case the component was already present the previous
+                /// one is returned.
+                pub fn insert(
+                    &mut self,
+                    idx: $crate::Entity,
+                    c: $name,
+                ) -> ::std::option::Option<$name> {
+                    if idx.0 >= self.present.len() {
+                        self.present.resize(idx.0 + 1, false);
+                        $(self.$field.resize_with(idx.0 + 1, ::std::default::Default::default);)*
+                    }
+
+                    let $name { $($field),* } = c;
+                    if ::std::mem::replace(&mut self.present[idx.0], true) {
+                        ::std::option::Option::Some($name {
+                            $($field: ::std::mem::replace(&mut self.$field[idx.0], $field)),*
+                        })
+                    } else {
+                        self.len += 1;
+                        $(self.$field[idx.0] = $field;)*
+                        ::std::option::Option::None
+                    }
+                }
+
+                /// Removes this component for the entity at `idx`.
+                pub fn remove(&mut self, idx: $crate::Entity) -> ::std::option::Option<$name> {
+                    if ::std::mem::replace(self.present.get_mut(idx.0)?, false) {
+                        self.len -= 1;
+                        ::std::option::Option::Some($name {
+                            $($field: ::std::mem::take(&mut self.$field[idx.0])),*
+                        })
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+
+                /// Splits this storage into a joinable column for each field.
+                pub fn columns(&self) -> Columns<'_> {
+                    Columns {
+                        $($field: $crate::soa::Column::new(&self.present, self.len, &self.$field)),*
+                    }
+                }
+
+                /// Splits this storage into a mutable joinable column for each field.
+                pub fn columns_mut(&mut self) -> ColumnsMut<'_> {
+                    ColumnsMut {
+                        $($field: $crate::soa::ColumnMut::new(
+                            &self.present,
+                            self.len,
+                            &mut self.$field,
+                        )),*
+                    }
+                }
+            }
+
+            /// The iterator created by joining a `&Storage`.
+            pub struct Iter<'a> {
+                mask: $crate::soa::Mask<'a>,
+                $($field: ::std::slice::Iter<'a, $ty>),*
+            }
+
+            impl<'a> ::std::iter::Iterator for Iter<'a> {
+                type Item = Ref<'a>;
+
+                fn next(&mut self) -> ::std::option::Option<Ref<'a>> {
+                    self.nth(0)
+                }
+
+                fn nth(&mut self, n: usize) -> ::std::option::Option<Ref<'a>> {
+                    let present = self.mask.nth(n);
+                    $(let $field = self.$field.nth(n);)*
+                    if present {
+                        ::std::option::Option::Some(Ref { $($field: $field?),* })
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+            }
+
+            impl<'a> $crate::Join for Iter<'a> {
+                fn may_skip(&mut self, _curr: usize) -> usize {
+                    self.mask.may_skip()
+                }
+
+                fn remaining(&self) -> (usize, ::std::option::Option<usize>) {
+                    self.mask.remaining()
+                }
+
+                fn truncate(&mut self, len: usize) {
+                    self.mask.truncate(len)
+                }
+
+                fn seek(&mut self, n: usize) {
+                    self.mask.seek(n);
+                    if n > 0 {
+                        $(self.$field.nth(n - 1);)*
+                    }
+                }
+            }
+
+            impl<'a> $crate::ExactJoin for Iter<'a> {}
+
+            impl<'a> $crate::Joinable for &'a Storage {
+                type Joined = Iter<'a>;
+                type Item = Ref<'a>;
+
+                fn join(self) -> $crate::Joined<Iter<'a>> {
+                    $crate::Joined::new(
+                        Iter {
+                            mask: $crate::soa::Mask::new(&self.present, self.len),
+                            $($field: self.$field.iter()),*
+                        },
+                        self.present.len(),
+                    )
+                }
+
+                fn fetch(self, entity: $crate::Entity) -> ::std::option::Option<Ref<'a>> {
+                    self.get(entity)
+                }
+            }
+
+            /// The iterator created by joining a `&mut Storage`.
+            pub struct IterMut<'a> {
+                mask: $crate::soa::Mask<'a>,
+                $($field: ::std::slice::IterMut<'a, $ty>),*
+            }
+
+            impl<'a> ::std::iter::Iterator for IterMut<'a> {
+                type Item = Mut<'a>;
+
+                fn next(&mut self) -> ::std::option::Option<Mut<'a>> {
+                    self.nth(0)
+                }
+
+                fn nth(&mut self, n: usize) -> ::std::option::Option<Mut<'a>> {
+                    let present = self.mask.nth(n);
+                    $(let $field = self.$field.nth(n);)*
+                    if present {
+                        ::std::option::Option::Some(Mut { $($field: $field?),* })
+                    } else {
+                        ::std::option::Option::None
+                    }
+                }
+            }
+
+            impl<'a> $crate::Join for IterMut<'a> {
+                fn may_skip(&mut self, _curr: usize) -> usize {
+                    self.mask.may_skip()
+                }
+
+                fn remaining(&self) -> (usize, ::std::option::Option<usize>) {
+                    self.mask.remaining()
+                }
+
+                fn truncate(&mut self, len: usize) {
+                    self.mask.truncate(len)
+                }
+
+                fn seek(&mut self, n: usize) {
+                    self.mask.seek(n);
+                    if n > 0 {
+                        $(self.$field.nth(n - 1);)*
+                    }
+                }
+            }
+
+            impl<'a> $crate::ExactJoin for IterMut<'a> {}
+
+            impl<'a> $crate::Joinable for &'a mut Storage {
+                type Joined = IterMut<'a>;
+                type Item = Mut<'a>;
+
+                fn join(self) -> $crate::Joined<IterMut<'a>> {
+                    let len = self.present.len();
+                    $crate::Joined::new(
+                        IterMut {
+                            mask: $crate::soa::Mask::new(&self.present, self.len),
+                            $($field: self.$field.iter_mut()),*
+                        },
+                        len,
+                    )
+                }
+
+                fn fetch(self, entity: $crate::Entity) -> ::std::option::Option<Mut<'a>> {
+                    self.get_mut(entity)
+                }
+            }
+        }
+    };
+}
+
+/// Tracks which entities of a struct-of-arrays storage have a component.
+///
+/// This is only public to be used by the iterators generated by
+/// [`soa_storage!`] and is not part of the stable API.
+///
+/// [`soa_storage!`]: ../macro.soa_storage.html
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct Mask<'a> {
+    present: &'a [bool],
+    /// The number of components in `present`.
+    len: usize,
+}
+
+impl<'a> Mask<'a> {
+    /// `len` has to be the number of `true` values in `present`.
+    pub fn new(present: &'a [bool], len: usize) -> Self {
+        Mask { present, len }
+    }
+
+    /// Skips `n` positions, returning whether the next one has a component.
+    pub fn nth(&mut self, n: usize) -> bool {
+        if self.present.len() > n {
+            let (start, end) = self.present.split_at(n + 1);
+            self.present = end;
+            let skipped = start.iter().filter(|&&p| p).count();
+            self.len -= skipped;
+            start[n]
+        } else {
+            self.present = &[];
+            self.len = 0;
+            false
+        }
+    }
+
+    /// Returns the number of positions without a component before the next one.
+    pub fn may_skip(&self) -> usize {
+        self.present.iter().take_while(|&&p| !p).count()
+    }
+
+    /// Returns the number of remaining components.
+    pub fn remaining(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    /// Ignores all but the next `len` positions, recounting the remaining components.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.present.len() {
+            self.present = &self.present[..len];
+        }
+        self.len = self.present.iter().filter(|&&p| p).count();
+    }
+
+    /// Skips `n` positions, only keeping an upper bound on the remaining components.
+    pub fn seek(&mut self, n: usize) {
+        self.present = self.present.get(n..).unwrap_or(&[]);
+    }
+
+    fn next_run(&self, from: usize) -> Option<Range<usize>> {
+        let rest = self.present.get(from..)?;
+        let start = from + rest.iter().position(|&p| p)?;
+        let len = self.present[start..].iter().take_while(|&&p| p).count();
+        Some(start..start + len)
+    }
+
+    fn advance(&mut self, n: usize) {
+        let (start, end) = self.present.split_at(n);
+        self.present = end;
+        self.len -= start.iter().filter(|&&p| p).count();
+    }
+}
+
+/// A single field of a struct-of-arrays storage, created using `Storage::columns`.
+#[derive(Debug, Clone)]
+pub struct Column<'a, F> {
+    mask: Mask<'a>,
+    values: slice::Iter<'a, F>,
+}
+
+impl<'a, F> Column<'a, F> {
+    /// Used by [`soa_storage!`], `len` has to be the number of `true` values in `present`.
+    ///
+    /// [`soa_storage!`]: ../macro.soa_storage.html
+    #[doc(hidden)]
+    pub fn new(present: &'a [bool], len: usize, values: &'a [F]) -> Self {
+        Column {
+            mask: Mask::new(present, len),
+            values: values.iter(),
+        }
+    }
+}
+
+impl<'a, F> Iterator for Column<'a, F> {
+    type Item = &'a F;
+
+    fn next(&mut self) -> Option<&'a F> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a F> {
+        let present = self.mask.nth(n);
+        self.values.nth(n).filter(|_| present)
+    }
+}
+
+impl<'a, F> Join for Column<'a, F> {
+    fn may_skip(&mut self, _curr: usize) -> usize {
+        self.mask.may_skip()
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        self.mask.remaining()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.mask.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.mask.seek(n);
+        if n > 0 {
+            self.values.nth(n - 1);
+        }
+    }
+}
+
+impl<'a, F> ExactJoin for Column<'a, F> {}
+
+impl<'a, F> ChunkJoin for Column<'a, F> {
+    type Chunk = &'a [F];
+
+    fn next_run(&self, from: usize) -> Option<Range<usize>> {
+        self.mask.next_run(from)
+    }
+
+    fn skip_run(&mut self, n: usize) {
+        self.take_run(n);
+    }
+
+    fn take_run(&mut self, n: usize) -> &'a [F] {
+        self.mask.advance(n);
+        let (chunk, rest) = self.values.as_slice().split_at(n);
+        self.values = rest.iter();
+        chunk
+    }
+}
+
+impl<'a, F> Joinable for Column<'a, F> {
+    type Joined = Self;
+    type Item = &'a F;
+
+    fn join(self) -> Joined<Self> {
+        let len = self.mask.present.len();
+        Joined::new(self, len)
+    }
+}
+
+/// A single mutable field of a struct-of-arrays storage, created using `Storage::columns_mut`.
+#[derive(Debug)]
+pub struct ColumnMut<'a, F> {
+    mask: Mask<'a>,
+    values: slice::IterMut<'a, F>,
+}
+
+impl<'a, F> ColumnMut<'a, F> {
+    /// Used by [`soa_storage!`], `len` has to be the number of `true` values in `present`.
+    ///
+    /// [`soa_storage!`]: ../macro.soa_storage.html
+    #[doc(hidden)]
+    pub fn new(present: &'a [bool], len: usize, values: &'a mut [F]) -> Self {
+        ColumnMut {
+            mask: Mask::new(present, len),
+            values: values.iter_mut(),
+        }
+    }
+}
+
+impl<'a, F> Iterator for ColumnMut<'a, F> {
+    type Item = &'a mut F;
+
+    fn next(&mut self) -> Option<&'a mut F> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a mut F> {
+        let present = self.mask.nth(n);
+        self.values.nth(n).filter(|_| present)
+    }
+}
+
+impl<'a, F> Join for ColumnMut<'a, F> {
+    fn may_skip(&mut self, _curr: usize) -> usize {
+        self.mask.may_skip()
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        self.mask.remaining()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.mask.truncate(len)
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.mask.seek(n);
+        if n > 0 {
+            self.values.nth(n - 1);
+        }
+    }
+}
+
+impl<'a, F> ExactJoin for ColumnMut<'a, F> {}
+
+impl<'a, F> ChunkJoin for ColumnMut<'a, F> {
+    type Chunk = &'a mut [F];
+
+    fn next_run(&self, from: usize) -> Option<Range<usize>> {
+        self.mask.next_run(from)
+    }
+
+    fn skip_run(&mut self, n: usize) {
+        self.take_run(n);
+    }
+
+    fn take_run(&mut self, n: usize) -> &'a mut [F] {
+        self.mask.advance(n);
+        let (chunk, rest) = mem::take(&mut self.values).into_slice().split_at_mut(n);
+        self.values = rest.iter_mut();
+        chunk
+    }
+}
+
+impl<'a, F> Joinable for ColumnMut<'a, F> {
+    type Joined = Self;
+    type Item = &'a mut F;
+
+    fn join(self) -> Joined<Self> {
+        let len = self.mask.present.len();
+        Joined::new(self, len)
+    }
+}
@@ -0,0 +1,397 @@
+//! A storage for positioned components supporting spatial queries.
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    drain::{Drain, OnRemove},
+    set::EntitySet,
+    Entity, Iter, Joinable, Joined, Storage,
+};
+
+/// A component with a position in 2d space.
+pub trait Position {
+    fn position(&self) -> (f32, f32);
+}
+
+impl Position for (f32, f32) {
+    fn position(&self) -> (f32, f32) {
+        *self
+    }
+}
+
+impl Position for [f32; 2] {
+    fn position(&self) -> (f32, f32) {
+        (self[0], self[1])
+    }
+}
+
+type Cell = (i32, i32);
+
+fn distance_squared(a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (dx, dy) = (a.0 - b.0, a.1 - b.1);
+    dx * dx + dy * dy
+}
+
+/// A storage which sorts its components into a uniform grid,
+/// allowing for efficient proximity queries.
+///
+/// # Examples
+///
+/// ```rust
+/// use crow_ecs::{spatial::GridStorage, Entity, Joinable, Storage};
+///
+/// let mut positions = GridStorage::with_cell_size(10.0);
+/// positions.insert(Entity(0), (0.0, 0.0));
+/// positions.insert(Entity(1), (3.0, 4.0));
+/// positions.insert(Entity(2), (50.0, 50.0));
+///
+/// let mut health = Storage::new();
+/// health.insert(Entity(1), 10);
+/// health.insert(Entity(2), 10);
+///
+/// // damage everything close to the explosion
+/// for (health, _) in (&mut health, positions.query_radius((0.0, 0.0), 5.0)).join() {
+///     *health -= 5;
+/// }
+///
+/// assert_eq!(health.get(Entity(1)), Some(&5));
+/// assert_eq!(positions.nearest_k((40.0, 40.0), 2), vec![Entity(2), Entity(1)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GridStorage<T> {
+    storage: Storage<T>,
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    len: usize,
+}
+
+impl<T: Position> Default for GridStorage<T> {
+    fn default() -> Self {
+        GridStorage::new()
+    }
+}
+
+impl<T: Position> GridStorage<T> {
+    /// Creates a new `GridStorage` with a cell size of `64.0`.
+    pub fn new() -> Self {
+        Self::with_cell_size(64.0)
+    }
+
+    /// Creates a new `GridStorage` with the given cell size.
+    ///
+    /// The cell size should be roughly the size of the usual queries.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `cell_size` is not positive.
+    pub fn with_cell_size(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "invalid cell size: {}", cell_size);
+        Self {
+            storage: Storage::new(),
+            cell_size,
+            cells: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    /// Removes all components in this storage.
+    pub fn clear(&mut self) {
+        self.storage.clear();
+        self.cells.clear();
+        self.len = 0;
+    }
+
+    /// Returns the number of components in this storage.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the component of the entity at `idx` in case it exists.
+    pub fn get(&self, idx: Entity) -> Option<&T> {
+        self.storage.get(idx)
+    }
+
+    /// Mutably accesses the component of the entity at `idx`.
+    ///
+    /// The grid is updated once the returned guard is dropped.
+    pub fn get_mut(&mut self, idx: Entity) -> Option<GridRefMut<'_, T>> {
+        let cell_size = self.cell_size;
+        let component = self.storage.get_mut(idx)?;
+        Some(GridRefMut {
+            entity: idx,
+            cell: cell_of(cell_size, component.position()),
+            cell_size,
+            component,
+            cells: &mut self.cells,
+        })
+    }
+
+    /// Inserts a component for the entity at `idx`.
+    ///
+    /// In case the component was already present the previous
+    /// one is returned.
+    pub fn insert(&mut self, idx: Entity, c: T) -> Option<T> {
+        let cell = self.cell_of(c.position());
+        let previous = self.storage.insert(idx, c);
+        if let Some(ref previous) = previous {
+            let previous = self.cell_of(previous.position());
+            remove_from_cell(&mut self.cells, previous, idx);
+        } else {
+            self.len += 1;
+        }
+        self.cells.entry(cell).or_default().push(idx);
+        previous
+    }
+
+    /// Removes this component for the entity at `idx`.
+    pub fn remove(&mut self, idx: Entity) -> Option<T> {
+        let c = self.storage.remove(idx)?;
+        let cell = self.cell_of(c.position());
+        remove_from_cell(&mut self.cells, cell, idx);
+        self.len -= 1;
+        Some(c)
+    }
+
+    /// Removes all components of this storage.
+    pub fn drain(&mut self) -> Drain<'_, T, RemoveFromCell<'_>> {
+        let GridStorage {
+            storage,
+            cell_size,
+            cells,
+            len,
+        } = self;
+        storage.drain_with(RemoveFromCell {
+            cell_size: *cell_size,
+            cells,
+            len,
+        })
+    }
+
+    /// Returns all entities inside of the axis aligned bounding box
+    /// from `min` to `max`, including its borders.
+    pub fn query_aabb(&self, min: (f32, f32), max: (f32, f32)) -> EntitySet {
+        let contains = |e: &&Entity| {
+            let (x, y) = self.position(**e);
+            min.0 <= x && x <= max.0 && min.1 <= y && y <= max.1
+        };
+
+        let (start, end) = (self.cell_of(min), self.cell_of(max));
+        let width = (i64::from(end.0) - i64::from(start.0) + 1).max(0);
+        let height = (i64::from(end.1) - i64::from(start.1) + 1).max(0);
+        let area = width
+            .checked_mul(height)
+            .and_then(|area| usize::try_from(area).ok());
+        // Overflowing areas contain far more cells than can be occupied.
+        if area.is_none_or(|area| area > self.cells.len()) {
+            self.cells
+                .values()
+                .flatten()
+                .filter(contains)
+                .copied()
+                .collect()
+        } else {
+            (start.0..=end.0)
+                .flat_map(|x| (start.1..=end.1).map(move |y| (x, y)))
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .filter(contains)
+                .copied()
+                .collect()
+        }
+    }
+
+    /// Returns all entities with a distance of at most `radius` to `center`.
+    pub fn query_radius(&self, center: (f32, f32), radius: f32) -> EntitySet {
+        let (min, max) = (
+            (center.0 - radius, center.1 - radius),
+            (center.0 + radius, center.1 + radius),
+        );
+        let mut entities = self.query_aabb(min, max);
+        let outside: Vec<_> = entities
+            .iter()
+            .filter(|&e| distance_squared(self.position(e), center) > radius * radius)
+            .collect();
+        for e in outside {
+            entities.remove(e);
+        }
+        entities
+    }
+
+    /// Returns up to `k` entities closest to `point`, ordered by their distance.
+    pub fn nearest_k(&self, point: (f32, f32), k: usize) -> Vec<Entity> {
+        let mut candidates = Vec::new();
+        let center = self.cell_of(point);
+        let mut ring = 0;
+        loop {
+            if ring_cells(ring) > self.cells.len() {
+                // Searching the remaining rings is more expensive
+                // than just checking every entity.
+                candidates = self.cells.values().flatten().copied().collect();
+                break;
+            }
+
+            candidates.extend(
+                ring_iter(center, ring)
+                    .filter_map(|cell| self.cells.get(&cell))
+                    .flatten()
+                    .copied(),
+            );
+
+            if candidates.len() == self.len {
+                break;
+            } else if candidates.len() >= k {
+                // Entities outside of the checked rings are at least
+                // this far away from `point`.
+                let min_distance = ring as f32 * self.cell_size;
+                let kth = self.sort_by_distance(&mut candidates, point, k);
+                if kth <= min_distance * min_distance {
+                    break;
+                }
+            }
+            ring += 1;
+        }
+
+        self.sort_by_distance(&mut candidates, point, k);
+        candidates.truncate(k);
+        candidates
+    }
+
+    /// Sorts `candidates` by their distance to `point`, returning the squared
+    /// distance of the `k`th closest one.
+    fn sort_by_distance(&self, candidates: &mut [Entity], point: (f32, f32), k: usize) -> f32 {
+        candidates.sort_by(|&a, &b| {
+            distance_squared(self.position(a), point)
+                .partial_cmp(&distance_squared(self.position(b), point))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        candidates
+            .get(k.wrapping_sub(1))
+            .map_or(0.0, |&e| distance_squared(self.position(e), point))
+    }
+
+    pub(crate) fn storage(&self) -> &Storage<T> {
+        &self.storage
+    }
+
+    fn position(&self, idx: Entity) -> (f32, f32) {
+        self.storage.get(idx).expect("missing component").position()
+    }
+
+    fn cell_of(&self, position: (f32, f32)) -> Cell {
+        cell_of(self.cell_size, position)
+    }
+}
+
+fn cell_of(cell_size: f32, (x, y): (f32, f32)) -> Cell {
+    (
+        (x / cell_size).floor() as i32,
+        (y / cell_size).floor() as i32,
+    )
+}
+
+/// Removes the components drained from a [`GridStorage`] from their cells.
+///
+/// [`GridStorage`]: struct.GridStorage.html
+#[derive(Debug)]
+pub struct RemoveFromCell<'a> {
+    cell_size: f32,
+    cells: &'a mut HashMap<Cell, Vec<Entity>>,
+    len: &'a mut usize,
+}
+
+impl<'a, T: Position> OnRemove<T> for RemoveFromCell<'a> {
+    fn removed(&mut self, idx: Entity, c: &T) {
+        remove_from_cell(self.cells, cell_of(self.cell_size, c.position()), idx);
+        *self.len -= 1;
+    }
+}
+
+fn remove_from_cell(cells: &mut HashMap<Cell, Vec<Entity>>, cell: Cell, idx: Entity) {
+    let entities = cells.get_mut(&cell).expect("missing grid entry");
+    let pos = entities
+        .iter()
+        .position(|&e| e == idx)
+        .expect("missing grid entry");
+    entities.swap_remove(pos);
+    if entities.is_empty() {
+        cells.remove(&cell);
+    }
+}
+
+/// The number of cells with a chebyshev distance of exactly `ring`.
+fn ring_cells(ring: i32) -> usize {
+    if ring == 0 {
+        1
+    } else {
+        8 * ring as usize
+    }
+}
+
+/// Returns the cells with a chebyshev distance of exactly `ring` to the given cell.
+///
+/// Cells outside of the range of `i32` are skipped, as `cell_of` never returns them.
+fn ring_iter((x, y): Cell, ring: i32) -> impl Iterator<Item = Cell> {
+    (-ring..=ring).flat_map(move |dx| {
+        let step = if dx.abs() == ring { 1 } else { 2 * ring.max(1) };
+        (-ring..=ring)
+            .step_by(step as usize)
+            .filter_map(move |dy| Some((x.checked_add(dx)?, y.checked_add(dy)?)))
+    })
+}
+
+/// A mutable reference to a component of a [`GridStorage`].
+///
+/// The grid is updated once this guard is dropped.
+///
+/// [`GridStorage`]: struct.GridStorage.html
+pub struct GridRefMut<'a, T: Position> {
+    entity: Entity,
+    cell: Cell,
+    cell_size: f32,
+    component: &'a mut T,
+    cells: &'a mut HashMap<Cell, Vec<Entity>>,
+}
+
+impl<'a, T: Position> Deref for GridRefMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.component
+    }
+}
+
+impl<'a, T: Position> DerefMut for GridRefMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.component
+    }
+}
+
+impl<'a, T: Position> Drop for GridRefMut<'a, T> {
+    fn drop(&mut self) {
+        let cell = cell_of(self.cell_size, self.component.position());
+        if cell != self.cell {
+            remove_from_cell(self.cells, self.cell, self.entity);
+            self.cells.entry(cell).or_default().push(self.entity);
+        }
+    }
+}
+
+impl<'a, T> Joinable for &'a GridStorage<T> {
+    type Joined = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn join(self) -> Joined<Self::Joined> {
+        (&self.storage).join()
+    }
+
+    fn fetch(self, entity: Entity) -> Option<&'a T> {
+        self.storage.get(entity)
+    }
+}
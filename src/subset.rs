@@ -0,0 +1,84 @@
+//! Joining only a selection of entities.
+use crate::{Entity, Join};
+
+/// The iterator created by [`Joinable::join_entities`].
+///
+/// [`Joinable::join_entities`]: ../trait.Joinable.html#method.join_entities
+#[derive(Debug, Clone)]
+pub struct Subset<T> {
+    iter: T,
+    /// The selected entities, sorted and without duplicates.
+    ids: Vec<usize>,
+    /// The index of the first selected entity at or after `position`.
+    next: usize,
+    position: usize,
+}
+
+impl<T> Subset<T> {
+    /// Selects `entities` of `iter`, which starts at `position`.
+    pub(crate) fn new(iter: T, position: usize, entities: &[Entity]) -> Self {
+        let mut ids: Vec<_> = entities.iter().map(|e| e.0).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let mut subset = Subset {
+            iter,
+            ids,
+            next: 0,
+            position,
+        };
+        subset.skip_to(position);
+        subset
+    }
+
+    /// The position after the last selected entity.
+    pub(crate) fn join_len(&self) -> usize {
+        self.ids.last().map_or(0, |v| v + 1)
+    }
+
+    fn skip_to(&mut self, position: usize) {
+        self.position = position;
+        while self.ids.get(self.next).is_some_and(|&k| k < position) {
+            self.next += 1;
+        }
+    }
+}
+
+impl<T: Join> Join for Subset<T> {
+    fn may_skip(&mut self, curr: usize) -> usize {
+        // Selected entities are looked up directly, so this does not
+        // search the joined storages for their next component.
+        self.skip_to(curr);
+        self.ids.get(self.next).map_or(usize::MAX, |&k| k - curr)
+    }
+
+    fn remaining(&self) -> (usize, Option<usize>) {
+        let selected = self.ids.len() - self.next;
+        let upper = self
+            .iter
+            .remaining()
+            .1
+            .map_or(selected, |v| v.min(selected));
+        (0, Some(upper))
+    }
+}
+
+impl<T: Iterator + Join> Iterator for Subset<T> {
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<T::Item> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<T::Item> {
+        self.iter.seek(n);
+        let item = self.iter.next();
+        self.skip_to(self.position.saturating_add(n));
+        self.position += 1;
+        if self.ids.get(self.next) == Some(&(self.position - 1)) {
+            self.next += 1;
+            item
+        } else {
+            None
+        }
+    }
+}
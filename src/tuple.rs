@@ -1,11 +1,11 @@
-use crate::{Join, Joinable, Joined};
+use crate::{DoubleEndedJoin, Join, Joinable, Joined};
 
 #[derive(Debug, Clone)]
 pub struct TupleJoin<T>(T);
 
 macro_rules! tuple_join {
     ($($par:ident $var:ident $e:tt),*) => {
-        impl<$($par: Iterator),*> Iterator for TupleJoin<($($par),*)>
+        impl<$($par: Iterator + Join),*> Iterator for TupleJoin<($($par),*)>
         {
             type Item = ($($par::Item),*);
 
@@ -13,30 +13,116 @@ macro_rules! tuple_join {
                 self.nth(0)
             }
 
+            // The last member's `found` is never read.
+            #[allow(unused_assignments)]
             fn nth(&mut self, n: usize) -> Option<Self::Item> {
-                match ($((self.0).$e.nth(n)),*) {
+                // Members which remove their items are only advanced
+                // once all other members yielded an item.
+                $(let $var = (self.0).$e.removes_nth(n);)*
+                if false $(|| $var == Some(false))* {
+                    $((self.0).$e.seek(n.saturating_add(1));)*
+                    return None;
+                }
+
+                $(let $var = match $var {
+                    None => Some((self.0).$e.nth(n)),
+                    Some(_) => None,
+                };)*
+                let mut found = true $(&& !matches!($var, Some(None)))*;
+                $(let $var = match $var {
+                    Some(item) => item,
+                    None if found => {
+                        let item = (self.0).$e.nth(n);
+                        found = item.is_some();
+                        item
+                    }
+                    None => {
+                        (self.0).$e.seek(n.saturating_add(1));
+                        None
+                    }
+                };)*
+                match ($($var),*) {
+                    ($(Some($var)),*) => Some(($($var),*)),
+                    _ => None,
+                }
+            }
+        }
+
+        impl<$($par: DoubleEndedIterator + Join),*> DoubleEndedIterator for TupleJoin<($($par),*)>
+        {
+            fn next_back(&mut self) -> Option<Self::Item> {
+                self.nth_back(0)
+            }
+
+            fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+                match ($((self.0).$e.nth_back(n)),*) {
                     ($(Some($var)),*) => Some(($($var),*)),
                     _ => None,
                 }
             }
         }
 
-        impl<$($par: Join),*> Join for TupleJoin<($($par),*)>
+        impl<$($par: Join + Iterator),*> Join for TupleJoin<($($par),*)>
         {
             fn may_skip(&mut self, curr: usize) -> usize {
-                std::usize::MIN.$(max((self.0).$e.may_skip(curr))).*
+                usize::MIN.$(max((self.0).$e.may_skip(curr))).*
+            }
+
+            fn remaining(&self) -> (usize, Option<usize>) {
+                let upper = None;
+                $(let upper = match ((self.0).$e.remaining().1, upper) {
+                    (Some(a), Some(b)) => Some(usize::min(a, b)),
+                    (a, b) => a.or(b),
+                };)*
+                (0, upper)
+            }
+
+            fn truncate(&mut self, len: usize) {
+                $((self.0).$e.truncate(len);)*
+            }
+
+            fn seek(&mut self, n: usize) {
+                $((self.0).$e.seek(n);)*
+            }
+
+            fn removes_nth(&mut self, n: usize) -> Option<bool> {
+                let mut removes = None;
+                $(match (self.0).$e.removes_nth(n) {
+                    Some(false) => return Some(false),
+                    Some(true) => removes = Some(true),
+                    None => {}
+                })*
+                removes
+            }
+        }
+
+        impl<$($par: DoubleEndedJoin),*> DoubleEndedJoin for TupleJoin<($($par),*)>
+        {
+            fn may_skip_back(&mut self) -> usize {
+                usize::MIN.$(max((self.0).$e.may_skip_back())).*
             }
         }
 
+        // `fetch` is not forwarded to the members, as those which remove their
+        // item may only do so once all other members have the entity.
         impl<$($par: Joinable),*> Joinable for ($($par),*)
         {
             type Joined = TupleJoin<($($par::Joined),*)>;
             type Item = ($($par::Item),*);
 
             fn join(self) -> Joined<Self::Joined> {
-                $(let $var = self.$e.join();)*
+                $(let mut $var = self.$e.join();)*
+
+                // Some joined iterators might not start at the first position,
+                // in which case all others have to skip ahead.
+                let pos = usize::MIN.$(max($var.pos)).*;
+                $($var.iter.seek(pos - $var.pos);)*
 
-                Joined::new(TupleJoin(($($var.iter),*)), std::usize::MAX.$(min($var.len)).*)
+                Joined::starting_at(
+                    TupleJoin(($($var.iter),*)),
+                    pos,
+                    usize::MAX.$(min($var.len)).*,
+                )
             }
         }
     }
@@ -50,3 +136,10 @@ tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5);
 tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6);
 tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7);
 tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8);
+tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9);
+tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10);
+tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11);
+tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11, M m 12);
+tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11, M m 12, N n 13);
+tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11, M m 12, N n 13, O o 14);
+tuple_join!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11, M m 12, N n 13, O o 14, P p 15);